@@ -1,8 +1,9 @@
+use chrono::NaiveDate;
 use clap::{Parser, Subcommand};
 
-use crate::{persona::Persona, provider::Provider};
+use crate::{provider::Provider, query::OutputFormat, shell_init::Shell, stats::StatsGroup};
 
-#[derive(Parser)]
+#[derive(Parser, Clone, Default)]
 #[command(name = "qq")]
 #[command(version)]
 #[command(about = "Query LLMs from the command line")]
@@ -14,21 +15,136 @@ pub struct Args {
     #[arg(short, long)]
     pub model: Option<String>,
 
-    /// Persona to use
+    /// Compare the answers of several models, e.g.
+    /// `gpt-4o,anthropic:claude-haiku-4-5`
+    #[arg(long, value_delimiter = ',', value_name = "MODELS", conflicts_with_all = ["model", "execute", "continue_session", "session"])]
+    pub models: Vec<String>,
+
+    /// Persona to use (built-in, from [personas.*] in the config or a persona
+    /// file)
     #[arg(short, long)]
-    pub persona: Option<Persona>,
+    pub persona: Option<String>,
 
     /// API key
     #[arg(short, long)]
     pub api_key: Option<String>,
 
+    /// File to attach to the prompt (can be repeated, accepts globs)
+    #[arg(short, long = "file", value_name = "PATH")]
+    pub files: Vec<String>,
+
+    /// Continue the previous conversation in this session
+    #[arg(short = 'c', long = "continue")]
+    pub continue_session: bool,
+
+    /// Offer to run the suggested command
+    #[arg(short = 'x', long)]
+    pub execute: bool,
+
+    /// Copy the response even if it looks dangerous
+    #[arg(long)]
+    pub force_copy: bool,
+
+    /// Send and log prompts without hiding secrets
+    #[arg(long)]
+    pub no_redact: bool,
+
+    /// Ask the model even if the answer is cached
+    #[arg(long)]
+    pub no_cache: bool,
+
+    /// Print the answer as text, or as one JSON object for scripts
+    #[arg(long, value_enum, default_value_t = OutputFormat::Text, conflicts_with_all = ["models", "execute"])]
+    pub output: OutputFormat,
+
+    /// Session name (defaults to one session per terminal)
+    #[arg(long, value_name = "NAME")]
+    pub session: Option<String>,
+
     /// Rest of the arguments to be combined into a single string
     #[arg(trailing_var_arg = true, allow_hyphen_values = true)]
     pub args: Vec<String>,
 }
 
-#[derive(Subcommand)]
+#[derive(Subcommand, Clone)]
 pub enum Commands {
+    /// Start an interactive chat (the default when run without a question)
+    Chat,
+    /// Suggest a fix for the last failed command
+    Fix {
+        /// Command to fix (defaults to the last one recorded by the shell
+        /// integration)
+        #[arg(long, value_name = "COMMAND")]
+        cmd: Option<String>,
+        /// Error output of the command (defaults to piped input)
+        #[arg(long, value_name = "TEXT")]
+        stderr: Option<String>,
+    },
+    /// Write a commit message for the staged changes and offer to commit
+    Commit,
+    /// List past requests from the request log
+    History {
+        #[command(subcommand)]
+        action: Option<HistoryAction>,
+
+        /// Only requests to models containing this name
+        #[arg(long)]
+        model: Option<String>,
+
+        /// Only requests on or after this date (YYYY-MM-DD)
+        #[arg(long, value_name = "DATE")]
+        since: Option<NaiveDate>,
+
+        /// Only requests on or before this date (YYYY-MM-DD)
+        #[arg(long, value_name = "DATE")]
+        until: Option<NaiveDate>,
+
+        /// Only failed requests
+        #[arg(long)]
+        errors: bool,
+
+        /// Only requests whose prompt or response contains this text
+        #[arg(short, long, value_name = "TEXT")]
+        search: Option<String>,
+
+        /// Number of requests to list
+        #[arg(short = 'n', long, default_value_t = 20)]
+        limit: usize,
+    },
+    /// Show spend, token usage and latency from the request log
+    Stats {
+        /// Group requests by day, model or provider
+        #[arg(long, value_enum, default_value_t = StatsGroup::Day)]
+        by: StatsGroup,
+    },
+    /// List the models of the active provider
+    Models {
+        /// Only models whose name contains this text
+        search: Option<String>,
+
+        /// Only free models
+        #[arg(long)]
+        free: bool,
+
+        /// Only models with at least this many tokens of context
+        #[arg(long, value_name = "TOKENS")]
+        min_context: Option<u32>,
+
+        /// Fetch the list again instead of using the one from the last day
+        #[arg(long)]
+        refresh: bool,
+    },
+    /// Manage cached answers
+    Cache {
+        #[command(subcommand)]
+        action: CacheAction,
+    },
+    /// Print a shell widget that turns the command line into a query
+    #[command(name = "shell-init")]
+    ShellInit {
+        /// Shell to print the widget for
+        shell: Shell,
+    },
     /// Configure provider and model settings
     #[command(name = "use")]
     Use {
@@ -37,21 +153,47 @@ pub enum Commands {
     },
 }
 
-#[derive(Subcommand)]
+#[derive(Subcommand, Clone)]
 pub enum UseTarget {
     /// Set the active provider
     Provider {
-        /// Provider name (e.g., "openrouter")
+        /// Provider name (e.g., "openrouter", or a custom [providers.*] name)
         name: Provider,
     },
     /// Set the model for the active provider
     Model {
         /// Model name (e.g., "anthropic/claude-3.5-sonnet")
         name: String,
+        /// Set the model even if the provider doesn't list it
+        #[arg(long)]
+        force: bool,
     },
     /// Set the API key for the active provider
     Key {
         /// API key for the current provider
         key: String,
+        /// Store the key in the OS keyring instead of the config file
+        #[arg(long)]
+        keyring: bool,
+    },
+}
+
+#[derive(Subcommand, Clone)]
+pub enum HistoryAction {
+    /// Print the response of a past request
+    Show {
+        /// Number shown by `qq history` (1 is the most recent)
+        number: usize,
+    },
+    /// Copy the response of a past request to the clipboard
+    Copy {
+        /// Number shown by `qq history` (1 is the most recent)
+        number: usize,
     },
 }
+
+#[derive(Subcommand, Clone)]
+pub enum CacheAction {
+    /// Remove every cached answer
+    Clear,
+}
@@ -0,0 +1,219 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime},
+};
+
+use anyhow::{Context, Result};
+use sha2::{Digest, Sha256};
+
+use crate::{
+    configs::{CacheConfig, Config},
+    provider::Message,
+    providers::build_openai_request,
+};
+
+/// Answers stored as text files named after a hash of the request
+pub struct ResponseCache {
+    dir: PathBuf,
+    ttl: Duration,
+    max_bytes: u64,
+}
+
+impl ResponseCache {
+    pub fn new(dir: impl Into<PathBuf>, settings: &CacheConfig) -> Self {
+        Self {
+            dir: dir.into(),
+            ttl: Duration::from_secs(settings.ttl_secs),
+            max_bytes: settings.max_size_mb * 1024 * 1024,
+        }
+    }
+
+    /// Hashes everything the answer depends on: the provider and the full
+    /// request sent to the model
+    pub fn key(config: &Config, system_prompt: &str, messages: &[Message]) -> Result<String> {
+        let request = build_openai_request(
+            &config.model,
+            config.persona.temperature,
+            system_prompt,
+            messages,
+            false,
+        )?;
+
+        let mut hasher = Sha256::new();
+        hasher.update(config.provider.to_string());
+        hasher.update([0]);
+        hasher.update(config.base_url.as_deref().unwrap_or_default());
+        hasher.update([0]);
+        hasher.update(serde_json::to_vec(&request)?);
+        Ok(hasher
+            .finalize()
+            .iter()
+            .map(|byte| format!("{:02x}", byte))
+            .collect())
+    }
+
+    /// Returns the stored answer unless it's older than the TTL
+    pub fn get(&self, key: &str) -> Option<String> {
+        let path = self.path(key);
+        if is_expired(&path, self.ttl) {
+            let _ = fs::remove_file(&path);
+            return None;
+        }
+        fs::read_to_string(path).ok()
+    }
+
+    /// Stores an answer, then drops the oldest ones once the cache outgrows
+    /// its maximum size
+    pub fn put(&self, key: &str, response: &str) -> Result<()> {
+        fs::create_dir_all(&self.dir)
+            .context(format!("Failed to create cache dir {:?}", self.dir))?;
+        let path = self.path(key);
+        fs::write(&path, response).context(format!("Failed to write cache entry {:?}", path))?;
+        self.trim()
+    }
+
+    /// Removes every stored answer and returns how many there were
+    pub fn clear(&self) -> Result<usize> {
+        let entries = self.entries()?;
+        for (path, _, _) in &entries {
+            fs::remove_file(path).context(format!("Failed to remove cache entry {:?}", path))?;
+        }
+        Ok(entries.len())
+    }
+
+    fn trim(&self) -> Result<()> {
+        let mut entries = self.entries()?;
+        let mut total: u64 = entries.iter().map(|(_, size, _)| size).sum();
+        entries.sort_by_key(|(_, _, modified)| *modified);
+
+        for (path, size, _) in entries {
+            if total <= self.max_bytes {
+                break;
+            }
+            fs::remove_file(&path).context(format!("Failed to remove cache entry {:?}", path))?;
+            total -= size;
+        }
+        Ok(())
+    }
+
+    /// Path, size and modification time of every stored answer
+    fn entries(&self) -> Result<Vec<(PathBuf, u64, SystemTime)>> {
+        if !self.dir.exists() {
+            return Ok(Vec::new());
+        }
+
+        let mut entries = Vec::new();
+        for entry in
+            fs::read_dir(&self.dir).context(format!("Failed to read cache dir {:?}", self.dir))?
+        {
+            let path = entry?.path();
+            if path.extension().is_none_or(|extension| extension != "txt") {
+                continue;
+            }
+            let metadata = fs::metadata(&path)?;
+            entries.push((path, metadata.len(), metadata.modified()?));
+        }
+        Ok(entries)
+    }
+
+    fn path(&self, key: &str) -> PathBuf {
+        self.dir.join(format!("{}.txt", key))
+    }
+}
+
+/// Whether the file at `path` was written more than `ttl` ago
+pub(crate) fn is_expired(path: &Path, ttl: Duration) -> bool {
+    fs::metadata(path)
+        .and_then(|metadata| metadata.modified())
+        .is_ok_and(|modified| modified.elapsed().unwrap_or_default() > ttl)
+}
+
+#[cfg(test)]
+#[cfg_attr(coverage_nightly, coverage(off))]
+mod tests {
+    use std::env;
+
+    use super::*;
+    use crate::provider::Provider;
+
+    fn temp_cache(name: &str, settings: &CacheConfig) -> ResponseCache {
+        let dir = env::temp_dir().join(format!("qq-cache-{}-{}", name, std::process::id()));
+        ResponseCache::new(dir, settings)
+    }
+
+    fn config(model: &str) -> Config {
+        Config {
+            provider: Provider::OpenRouter,
+            model: model.to_string(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_key_depends_on_the_request() {
+        let messages = [Message::user("tar extract gz")];
+        let key = ResponseCache::key(&config("a"), "system", &messages).unwrap();
+
+        assert_eq!(key.len(), 64);
+        assert_eq!(
+            ResponseCache::key(&config("a"), "system", &messages).unwrap(),
+            key
+        );
+        assert_ne!(
+            ResponseCache::key(&config("b"), "system", &messages).unwrap(),
+            key
+        );
+        assert_ne!(
+            ResponseCache::key(&config("a"), "other system", &messages).unwrap(),
+            key
+        );
+        let other_question = [Message::user("tar create gz")];
+        assert_ne!(
+            ResponseCache::key(&config("a"), "system", &other_question).unwrap(),
+            key
+        );
+    }
+
+    #[test]
+    fn test_put_get_and_clear() {
+        let cache = temp_cache("roundtrip", &CacheConfig::default());
+
+        assert_eq!(cache.get("abc"), None);
+        cache.put("abc", "tar -xzf archive.tar.gz").unwrap();
+        assert_eq!(cache.get("abc").as_deref(), Some("tar -xzf archive.tar.gz"));
+
+        assert_eq!(cache.clear().unwrap(), 1);
+        assert_eq!(cache.get("abc"), None);
+        fs::remove_dir_all(&cache.dir).unwrap();
+    }
+
+    #[test]
+    fn test_expired_entries_are_dropped() {
+        let settings = CacheConfig {
+            ttl_secs: 0,
+            ..Default::default()
+        };
+        let cache = temp_cache("expired", &settings);
+
+        cache.put("abc", "ls").unwrap();
+        std::thread::sleep(Duration::from_millis(10));
+        assert_eq!(cache.get("abc"), None);
+        assert!(!cache.path("abc").exists());
+        fs::remove_dir_all(&cache.dir).unwrap();
+    }
+
+    #[test]
+    fn test_trim_drops_oldest_entries() {
+        let mut cache = temp_cache("trim", &CacheConfig::default());
+        cache.max_bytes = 10;
+
+        cache.put("old", "123456").unwrap();
+        std::thread::sleep(Duration::from_millis(10));
+        cache.put("new", "123456").unwrap();
+
+        assert_eq!(cache.get("old"), None);
+        assert_eq!(cache.get("new").as_deref(), Some("123456"));
+        fs::remove_dir_all(&cache.dir).unwrap();
+    }
+}
@@ -0,0 +1,249 @@
+use std::{fs, path::PathBuf, process::Command};
+
+use anyhow::{Context, Result, anyhow};
+use rustyline::{DefaultEditor, error::ReadlineError};
+
+use crate::{execute::strip_code_fence, logging::RequestLogEntryBuilder};
+
+/// Amount of diff sent to the model, leaving room for the answer in small
+/// context windows
+const MAX_DIFF_BYTES: usize = 24 * 1024;
+
+/// Returns the changes staged for the next commit
+pub fn staged_diff() -> Result<String> {
+    let output = Command::new("git")
+        .args(["diff", "--staged"])
+        .output()
+        .context("Failed to run git")?;
+
+    if !output.status.success() {
+        return Err(anyhow!(
+            "git diff failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+
+    let diff = String::from_utf8_lossy(&output.stdout).into_owned();
+    if diff.trim().is_empty() {
+        return Err(anyhow!(
+            "Nothing staged to commit\n\nStage your changes with: git add"
+        ));
+    }
+    Ok(diff)
+}
+
+/// Returns the repository's git directory, where the message is kept while
+/// git commits it
+fn git_dir() -> Result<PathBuf> {
+    let output = Command::new("git")
+        .args(["rev-parse", "--git-dir"])
+        .output()
+        .context("Failed to run git")?;
+
+    if !output.status.success() {
+        return Err(anyhow!(
+            "git rev-parse failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    Ok(PathBuf::from(
+        String::from_utf8_lossy(&output.stdout).trim(),
+    ))
+}
+
+pub fn build_commit_prompt(diff: &str) -> String {
+    format!(
+        "Write a commit message for these staged changes:\n\n{}",
+        truncate_diff(diff, MAX_DIFF_BYTES)
+    )
+}
+
+/// Shortens a diff to at most about `max_bytes`, sharing the space between
+/// files so that every file is represented. Small files are kept whole and
+/// large ones are cut off at a line boundary.
+fn truncate_diff(diff: &str, max_bytes: usize) -> String {
+    if diff.len() <= max_bytes {
+        return diff.to_string();
+    }
+
+    let files = split_files(diff);
+    let mut by_size: Vec<usize> = (0..files.len()).collect();
+    by_size.sort_by_key(|&i| files[i].len());
+
+    // Give every file an equal share, passing on what small files don't use
+    let mut budgets = vec![0; files.len()];
+    let mut remaining = max_bytes;
+    for (position, &i) in by_size.iter().enumerate() {
+        let share = remaining / (files.len() - position);
+        budgets[i] = files[i].len().min(share);
+        remaining -= budgets[i];
+    }
+
+    files
+        .iter()
+        .zip(budgets)
+        .map(|(file, budget)| truncate_file(file, budget))
+        .collect()
+}
+
+/// Splits a diff into one chunk per file, each starting with `diff --git`
+fn split_files(diff: &str) -> Vec<&str> {
+    let mut starts: Vec<usize> = diff
+        .match_indices("\ndiff --git ")
+        .map(|(i, _)| i + 1)
+        .collect();
+    starts.insert(0, 0);
+    starts.push(diff.len());
+
+    starts
+        .windows(2)
+        .map(|bounds| &diff[bounds[0]..bounds[1]])
+        .filter(|file| !file.is_empty())
+        .collect()
+}
+
+fn truncate_file(file: &str, max_bytes: usize) -> String {
+    if file.len() <= max_bytes {
+        return file.to_string();
+    }
+
+    let mut kept = String::new();
+    for line in file.split_inclusive('\n') {
+        if kept.len() + line.len() > max_bytes {
+            break;
+        }
+        kept.push_str(line);
+    }
+
+    let omitted = file[kept.len()..].lines().count();
+    kept.push_str(&format!("[... {} more lines ...]\n", omitted));
+    kept
+}
+
+/// What to do with the suggested commit message
+#[derive(Debug, PartialEq)]
+enum Choice {
+    Commit,
+    Edit,
+    Quit,
+}
+
+impl Choice {
+    fn parse(input: &str) -> Option<Self> {
+        match input.trim().to_lowercase().as_str() {
+            "c" | "commit" => Some(Self::Commit),
+            "e" | "edit" => Some(Self::Edit),
+            "q" | "quit" | "" => Some(Self::Quit),
+            _ => None,
+        }
+    }
+}
+
+/// Commits the staged changes with the suggested message once approved,
+/// opening the user's git editor first when they want to edit it
+pub fn confirm_and_commit(response: &str, log_entry: &mut RequestLogEntryBuilder) -> Result<()> {
+    let message = strip_code_fence(response);
+    if message.is_empty() {
+        return Ok(());
+    }
+
+    let mut editor = DefaultEditor::new().context("Failed to start line editor")?;
+    let edit = loop {
+        let choice = match editor.readline("\x1b[1m[c]ommit / [e]dit / [q]uit:\x1b[0m ") {
+            Ok(input) => input,
+            Err(ReadlineError::Interrupted | ReadlineError::Eof) => return Ok(()),
+            Err(err) => return Err(err).context("Failed to read input"),
+        };
+
+        match Choice::parse(&choice) {
+            Some(Choice::Commit) => break false,
+            Some(Choice::Edit) => break true,
+            Some(Choice::Quit) => return Ok(()),
+            None => continue,
+        }
+    };
+
+    // The message goes through a file rather than stdin, as the editor git
+    // opens needs the terminal. It's kept in the git directory like git's own
+    // COMMIT_EDITMSG.
+    let message_file = git_dir()?.join("QQ_COMMIT_EDITMSG");
+    fs::write(&message_file, &message).context("Failed to write the commit message")?;
+    let mut command = Command::new("git");
+    command.arg("commit").arg("-F").arg(&message_file);
+    if edit {
+        command.arg("--edit");
+    }
+
+    let status = command.status();
+    let _ = fs::remove_file(&message_file);
+    let status = status.context("Failed to run git commit")?;
+
+    let edit_flag = if edit { " --edit" } else { "" };
+    log_entry.executed_command(format!("git commit{}\n\n{}", edit_flag, message));
+    log_entry.exit_code(status.code());
+    Ok(())
+}
+
+#[cfg(test)]
+#[cfg_attr(coverage_nightly, coverage(off))]
+mod tests {
+    use super::*;
+
+    fn file_diff(name: &str, lines: usize) -> String {
+        let mut diff = format!("diff --git a/{name} b/{name}\n--- a/{name}\n+++ b/{name}\n");
+        for i in 0..lines {
+            diff.push_str(&format!("+line {i}\n"));
+        }
+        diff
+    }
+
+    #[test]
+    fn test_parse_choice() {
+        assert_eq!(Choice::parse("c"), Some(Choice::Commit));
+        assert_eq!(Choice::parse("Edit"), Some(Choice::Edit));
+        assert_eq!(Choice::parse(""), Some(Choice::Quit));
+        assert_eq!(Choice::parse("r"), None);
+    }
+
+    #[test]
+    fn test_split_files() {
+        let diff = format!("{}{}", file_diff("a.rs", 1), file_diff("b.rs", 2));
+        let files = split_files(&diff);
+        assert_eq!(files.len(), 2);
+        assert!(files[0].starts_with("diff --git a/a.rs"));
+        assert!(files[1].starts_with("diff --git a/b.rs"));
+    }
+
+    #[test]
+    fn test_truncate_small_diff() {
+        let diff = file_diff("a.rs", 3);
+        assert_eq!(truncate_diff(&diff, 1024), diff);
+    }
+
+    #[test]
+    fn test_truncate_keeps_every_file() {
+        let small = file_diff("small.rs", 2);
+        let diff = format!(
+            "{}{}{}",
+            file_diff("big.rs", 500),
+            small,
+            file_diff("z.rs", 500)
+        );
+
+        let truncated = truncate_diff(&diff, 2048);
+        assert!(truncated.len() < 2048 + 100);
+        assert!(truncated.contains("diff --git a/big.rs"));
+        assert!(truncated.contains(&small));
+        assert!(truncated.contains("diff --git a/z.rs"));
+        assert!(truncated.contains("more lines ...]"));
+    }
+
+    #[test]
+    fn test_truncate_file_at_line_boundary() {
+        let file = "line one\nline two\nline three\n";
+        assert_eq!(
+            truncate_file(file, 12),
+            "line one\n[... 2 more lines ...]\n"
+        );
+    }
+}
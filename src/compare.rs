@@ -0,0 +1,199 @@
+use std::{
+    io::{self, IsTerminal},
+    path::PathBuf,
+    time::{Duration, Instant},
+};
+
+use anyhow::Result;
+use chrono::Local;
+use futures::future::join_all;
+use spinoff::{Color, Spinner, Streams, spinners};
+
+use crate::{
+    configs::{Config, Target},
+    logging::{RequestLogEntryBuilder, write_log_entry},
+    pricing::request_cost,
+    prompts::get_system_prompt,
+    provider::{Completion, Message, TokenUsage},
+    providers::create_provider,
+    retry::with_retries,
+    safety::analyze,
+    system_info::SystemInfo,
+};
+
+/// One model's answer to the compared question
+struct Answer {
+    config: Config,
+    result: Result<Completion>,
+    retries: u32,
+    latency: Duration,
+}
+
+/// Sends the question to every model given with `--models` at once, then
+/// prints the answers one after another and logs one request per model.
+/// Returns whether every model answered.
+pub async fn compare_models(
+    config: &Config,
+    user_prompt: &str,
+    attached_files: &[PathBuf],
+) -> bool {
+    let start = Instant::now();
+    let time = Local::now().to_rfc3339();
+    let system_info = SystemInfo::detect(&config.system);
+    let system_prompt = get_system_prompt(&config.persona, &system_info);
+    let messages = [Message::user(user_prompt)];
+
+    let stream = if io::stdout().is_terminal() {
+        Streams::Stdout
+    } else {
+        Streams::Stderr
+    };
+    let mut spinner = Spinner::new_with_stream(
+        spinners::Dots,
+        format!("Asking {} models", config.models.len()),
+        Color::Blue,
+        stream,
+    );
+    let answers = join_all(
+        config
+            .models
+            .iter()
+            .map(|target| ask_model(config, target, &system_prompt, &messages)),
+    )
+    .await;
+    spinner.clear();
+
+    for answer in &answers {
+        let mut log_entry = RequestLogEntryBuilder::default();
+        log_entry.time(&time);
+        log_entry.config(&answer.config);
+        log_entry.user_prompt(user_prompt);
+        log_entry.attached_files(attached_files.to_vec());
+        print_answer(answer, &mut log_entry);
+        write_log_entry(&mut log_entry, start, config.log_file.as_ref());
+    }
+
+    answers.iter().all(|answer| answer.result.is_ok())
+}
+
+async fn ask_model(
+    config: &Config,
+    target: &Target,
+    system_prompt: &str,
+    messages: &[Message],
+) -> Answer {
+    let start = Instant::now();
+    let mut retries = 0;
+    let (config, result) = match config.with_target(target) {
+        Ok(config) => {
+            let result = match create_provider(&config) {
+                Ok(provider) => {
+                    with_retries(&config.retry, &mut retries, || {
+                        provider.prompt(system_prompt, messages)
+                    })
+                    .await
+                }
+                Err(err) => Err(err),
+            };
+            (config, result)
+        }
+        // A model whose API key can't be found gets a failed answer, the
+        // others are still asked
+        Err(err) => (
+            Config {
+                provider: target.provider.clone(),
+                model: target.model.clone(),
+                ..config.clone()
+            },
+            Err(err),
+        ),
+    };
+
+    Answer {
+        config,
+        result,
+        retries,
+        latency: start.elapsed(),
+    }
+}
+
+/// Prints the answer under a header naming the model, and records it in the
+/// model's log entry
+fn print_answer(answer: &Answer, log_entry: &mut RequestLogEntryBuilder) {
+    let config = &answer.config;
+    let usage = answer
+        .result
+        .as_ref()
+        .ok()
+        .and_then(|completion| completion.usage);
+    println!("{}", header(config, answer.latency, usage));
+
+    log_entry.llm_response_time_ms(answer.latency.as_millis() as u64);
+    log_entry.retries(answer.retries);
+    match &answer.result {
+        Ok(completion) => {
+            println!("{}", completion.content);
+            let warnings = analyze(&completion.content, &config.safety_rules);
+            for warning in &warnings {
+                eprintln!("\x1b[1;31mWarning:\x1b[0m \x1b[31m{warning}\x1b[0m");
+            }
+
+            log_entry.response(config.redactor.redact(&completion.content).0);
+            log_entry.usage(usage);
+            log_entry.cost_usd(
+                usage.and_then(|usage| request_cost(&usage, &config.model, &config.prices)),
+            );
+            log_entry.safety_warnings(warnings.into_iter().map(String::from).collect::<Vec<_>>());
+        }
+        Err(err) => {
+            println!("\x1b[31m{:#}\x1b[0m", err);
+            log_entry.error(format!("{:?}", err));
+        }
+    }
+    println!();
+}
+
+/// E.g. "── gpt-4o  openai · 1.2s · 148 → 12 tokens"
+fn header(config: &Config, latency: Duration, usage: Option<TokenUsage>) -> String {
+    let mut details = format!("{} · {:.1}s", config.provider, latency.as_secs_f64());
+    if let Some(usage) = usage {
+        details.push_str(&format!(
+            " · {} → {} tokens",
+            usage.prompt_tokens, usage.completion_tokens
+        ));
+    }
+    format!(
+        "\x1b[1m── {}\x1b[0m  \x1b[90m{}\x1b[0m",
+        config.model, details
+    )
+}
+
+#[cfg(test)]
+#[cfg_attr(coverage_nightly, coverage(off))]
+mod tests {
+    use super::*;
+    use crate::provider::Provider;
+
+    #[test]
+    fn test_header() {
+        let config = Config {
+            provider: Provider::OpenAI,
+            model: "gpt-4o".to_string(),
+            ..Default::default()
+        };
+        let usage = TokenUsage {
+            prompt_tokens: 148,
+            completion_tokens: 12,
+            cost: None,
+        };
+
+        assert_eq!(
+            header(&config, Duration::from_millis(1234), Some(usage)),
+            "\x1b[1m── gpt-4o\x1b[0m  \x1b[90mopenai · 1.2s · 148 → 12 tokens\x1b[0m"
+        );
+        assert_eq!(
+            header(&config, Duration::from_millis(50), None),
+            "\x1b[1m── gpt-4o\x1b[0m  \x1b[90mopenai · 0.1s\x1b[0m"
+        );
+    }
+}
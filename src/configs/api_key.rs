@@ -0,0 +1,133 @@
+use anyhow::{Context, Result, anyhow};
+use keyring::Entry;
+
+use crate::{configs::types::ProviderConfig, execute::shell_command, provider::Provider};
+
+/// Service name of the keyring entries, one per provider
+const KEYRING_SERVICE: &str = "qq";
+
+impl ProviderConfig {
+    /// Looks up the API key from the first configured source: an environment
+    /// variable, a command, the OS keyring and finally the plaintext `api_key`
+    pub fn resolve_api_key(
+        &self,
+        provider: &Provider,
+        env: impl Fn(&str) -> Option<String>,
+    ) -> Result<String> {
+        if let Some(var) = &self.api_key_env {
+            return env(var)
+                .filter(|value| !value.trim().is_empty())
+                .map(|value| value.trim().to_string())
+                .ok_or_else(|| anyhow!("Environment variable {} is not set", var));
+        }
+        if let Some(command) = &self.api_key_cmd {
+            return run_key_command(command);
+        }
+        if self.api_key_keyring {
+            return read_keyring(provider);
+        }
+        Ok(self.api_key.clone())
+    }
+}
+
+/// Saves the API key of a provider in the OS keyring (Secret Service on Linux)
+pub fn store_in_keyring(provider: &Provider, api_key: &str) -> Result<()> {
+    keyring_entry(provider)?
+        .set_password(api_key)
+        .map_err(|err| anyhow!("Failed to store the API key in the keyring: {}", err))
+}
+
+fn read_keyring(provider: &Provider) -> Result<String> {
+    keyring_entry(provider)?.get_password().map_err(|err| {
+        anyhow!(
+            "Failed to read the API key from the keyring: {}\nStore it with: qq use key --keyring YOUR_API_KEY",
+            err
+        )
+    })
+}
+
+fn keyring_entry(provider: &Provider) -> Result<Entry> {
+    Entry::new(KEYRING_SERVICE, &provider.to_string())
+        .map_err(|err| anyhow!("Failed to open the keyring: {}", err))
+}
+
+/// Runs `api_key_cmd` in the user's shell and takes the first line it prints
+fn run_key_command(command: &str) -> Result<String> {
+    let output = shell_command(command)
+        .output()
+        .context(format!("Failed to run '{}'", command))?;
+    if !output.status.success() {
+        return Err(anyhow!(
+            "'{}' failed: {}",
+            command,
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+
+    // Tools like `pass` print extra fields after the key
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let api_key = stdout.lines().next().unwrap_or_default().trim();
+    if api_key.is_empty() {
+        return Err(anyhow!("'{}' printed no API key", command));
+    }
+    Ok(api_key.to_string())
+}
+
+#[cfg(test)]
+#[cfg_attr(coverage_nightly, coverage(off))]
+mod tests {
+    use super::*;
+
+    fn provider_config() -> ProviderConfig {
+        ProviderConfig {
+            api_key: "plaintext-key".to_string(),
+            model: "gpt-4".to_string(),
+            ..Default::default()
+        }
+    }
+
+    fn no_env(_: &str) -> Option<String> {
+        None
+    }
+
+    #[test]
+    fn test_plaintext_api_key() {
+        let api_key = provider_config().resolve_api_key(&Provider::OpenRouter, no_env);
+        assert_eq!(api_key.unwrap(), "plaintext-key");
+    }
+
+    #[test]
+    fn test_api_key_from_env() {
+        let config = ProviderConfig {
+            api_key_env: Some("OPENROUTER_API_KEY".to_string()),
+            ..provider_config()
+        };
+
+        let env = |var: &str| (var == "OPENROUTER_API_KEY").then(|| "sk-env\n".to_string());
+        let api_key = config.resolve_api_key(&Provider::OpenRouter, env);
+        assert_eq!(api_key.unwrap(), "sk-env");
+        assert!(
+            config
+                .resolve_api_key(&Provider::OpenRouter, no_env)
+                .is_err()
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_api_key_from_command() {
+        let config = ProviderConfig {
+            api_key_cmd: Some("printf 'sk-cmd\\nlogin: me\\n'".to_string()),
+            ..provider_config()
+        };
+        let api_key = config.resolve_api_key(&Provider::OpenRouter, no_env);
+        assert_eq!(api_key.unwrap(), "sk-cmd");
+
+        let config = ProviderConfig {
+            api_key_cmd: Some("echo locked >&2; exit 1".to_string()),
+            ..provider_config()
+        };
+        let err = config.resolve_api_key(&Provider::OpenRouter, no_env);
+        assert!(err.unwrap_err().to_string().contains("locked"));
+    }
+}
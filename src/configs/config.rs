@@ -1,22 +1,101 @@
-use std::path::PathBuf;
+use std::{collections::HashMap, path::PathBuf};
 
 use anyhow::{Result, anyhow};
 use derive_builder::Builder;
 
-use crate::{Persona, args::Args, configs::config_file::ConfigFile, provider::Provider};
+use crate::{
+    args::Args,
+    configs::{
+        CacheConfig, ModelPrice, RetryConfig, SystemConfig, config_file::ConfigFile,
+        types::ProviderConfig,
+    },
+    persona::{DEFAULT_PERSONA, Persona},
+    pricing::price_table,
+    provider::Provider,
+    query::OutputFormat,
+    redact::Redactor,
+    safety::SafetyRule,
+};
 
-#[derive(Builder, Debug, Default)]
+#[derive(Builder, Debug, Default, Clone)]
 #[builder(setter(into))]
 pub struct Config {
     pub provider: Provider,
     pub model: String,
     pub api_key: String,
-    pub persona: Option<Persona>,
+    pub base_url: Option<String>,
+    pub headers: HashMap<String, String>,
+    pub persona: Persona,
     pub auto_copy: bool,
     pub log_file: Option<PathBuf>,
+    pub system: SystemConfig,
+    pub safety_rules: Vec<SafetyRule>,
+    /// Copy responses flagged as dangerous too
+    pub force_copy: bool,
+    /// Prices per model, for estimating the cost of requests
+    pub prices: HashMap<String, ModelPrice>,
+    /// Hides secrets in prompts and the log, unless disabled with `--no-redact`
+    pub redactor: Redactor,
+    pub retry: RetryConfig,
+    /// Models to ask, in order, when the selected one fails
+    pub fallback: Vec<Target>,
+    /// Models to compare, from `--models`
+    pub models: Vec<Target>,
+    pub cache: CacheConfig,
+    /// Every configured provider, for reaching fallback and compared models
+    pub providers: HashMap<Provider, ProviderConfig>,
+    /// How answers are printed, from `--output`
+    pub output: OutputFormat,
+}
+
+/// A provider and model to send requests to. How to reach it is only looked
+/// up when it's asked, so a missing key doesn't break other requests.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Target {
+    pub provider: Provider,
+    pub model: String,
 }
 
 impl Config {
+    /// The same settings, sending requests to another model instead. The
+    /// selected provider's key is reused, as it may come from the command line.
+    pub fn with_target(&self, target: &Target) -> Result<Self> {
+        let mut config = Self {
+            provider: target.provider.clone(),
+            model: target.model.clone(),
+            fallback: Vec::new(),
+            models: Vec::new(),
+            ..self.clone()
+        };
+        if target.provider == self.provider {
+            return Ok(config);
+        }
+
+        let provider = &target.provider;
+        let provider_config = self.providers.get(provider).ok_or_else(|| {
+            anyhow!(
+                "Provider '{}' not found in config\nAdd a [providers.{}] section to your config",
+                provider,
+                provider
+            )
+        })?;
+        config.api_key = provider_config
+            .resolve_api_key(provider, |var| std::env::var(var).ok())
+            .map_err(|err| {
+                anyhow!(
+                    "Failed to get the API key for provider '{}': {}",
+                    provider,
+                    err
+                )
+            })?;
+        if provider.requires_api_key() && config.api_key.trim().is_empty() {
+            return Err(anyhow!("API key not set for provider '{}'", provider));
+        }
+        config.base_url = provider_config.base_url.clone();
+        config.headers = provider_config.headers.clone().unwrap_or_default();
+        Ok(config)
+    }
+
     pub fn from_config_file(config_file: &ConfigFile, args: &Args) -> Result<Self> {
         // Get provider name
         let provider = config_file.provider.as_ref().ok_or_else(|| {
@@ -26,7 +105,7 @@ impl Config {
         // Get providers map
         let providers = config_file.providers.as_ref().ok_or_else(|| {
             anyhow!(
-                "No providers configured in config at {:?}\nAdd a [providers.{:?}] section",
+                "No providers configured in config at {:?}\nAdd a [providers.{}] section",
                 "config.toml",
                 provider
             )
@@ -34,47 +113,139 @@ impl Config {
 
         // Get selected provider config
         let provider_config = providers.get(provider).ok_or_else(|| {
-            let available: Vec<_> = providers.keys().map(|p| format!("{:?}", p)).collect();
+            let available: Vec<_> = providers.keys().map(|p| p.to_string()).collect();
             anyhow!(
-                "Provider '{:?}' not found in config\n\nAvailable providers: {}\nCheck your config at {:?}",
+                "Provider '{}' not found in config\n\nAvailable providers: {}\nCheck your config at {:?}",
                 provider,
                 available.join(", "),
                 "config.toml"
             )
         })?.clone();
 
-        // Check if API key is set (unless overridden by CLI args)
-        if args.api_key.is_none() && provider_config.api_key.trim().is_empty() {
+        // CLI args override the configured key sources
+        let api_key = match &args.api_key {
+            Some(api_key) => api_key.clone(),
+            None => provider_config
+                .resolve_api_key(provider, |var| std::env::var(var).ok())
+                .map_err(|err| {
+                    anyhow!(
+                        "Failed to get the API key for provider '{}': {}",
+                        provider,
+                        err
+                    )
+                })?,
+        };
+
+        // Check if API key is set (unless not needed)
+        if provider.requires_api_key() && api_key.trim().is_empty() {
             return Err(anyhow!(
-                "API key not set for provider '{:?}'\n\nSet your API key with: qq use key YOUR_API_KEY\nOr edit your config at {:?}",
+                "API key not set for provider '{}'\n\nSet your API key with: qq use key YOUR_API_KEY\nOr set api_key, api_key_env or api_key_cmd in your config at {:?}",
                 provider,
                 "config.toml"
             ));
         }
 
+        // Resolve persona (CLI args override) from the config or the built-ins
+        let persona_name = args
+            .persona
+            .as_deref()
+            .or(config_file.persona.as_deref())
+            .unwrap_or(DEFAULT_PERSONA);
+        let persona = config_file
+            .personas
+            .as_ref()
+            .and_then(|personas| personas.get(persona_name))
+            .map(|persona_config| Persona::from_config(persona_name, persona_config))
+            .or_else(|| Persona::builtin(persona_name))
+            .ok_or_else(|| {
+                anyhow!(
+                    "Persona '{}' not found\n\nAdd a [personas.{}] section with a system_prompt to your config at {:?}\nOr create personas/{}.md next to it",
+                    persona_name,
+                    persona_name,
+                    "config.toml",
+                    persona_name
+                )
+            })?;
+
         // Build config with provider values
         let mut config_builder = ConfigBuilder::default();
         config_builder
             .provider(provider.clone())
             .model(provider_config.model)
-            .api_key(provider_config.api_key);
+            .api_key(api_key.clone())
+            .base_url(provider_config.base_url)
+            .headers(provider_config.headers.unwrap_or_default());
 
-        if let Some(persona) = config_file.persona {
-            config_builder.persona(persona);
+        // Persona settings take precedence over the provider's
+        if let Some(model) = &persona.model {
+            config_builder.model(model);
         }
+        config_builder.persona(persona);
+
         config_builder.auto_copy(config_file.auto_copy);
         config_builder.log_file(config_file.log_file.clone());
+        config_builder.system(config_file.system.clone().unwrap_or_default());
+        config_builder.safety_rules(SafetyRule::all(
+            config_file.safety_rules.as_deref().unwrap_or_default(),
+        )?);
+        config_builder.force_copy(args.force_copy);
+        config_builder.prices(price_table(config_file.prices.as_ref()));
+        config_builder.redactor(if args.no_redact {
+            Redactor::default()
+        } else {
+            Redactor::new(config_file.redaction_rules.as_deref().unwrap_or_default())?
+        });
+        let retry = config_file.retry.unwrap_or_default();
+        if retry.timeout_secs == 0 {
+            return Err(anyhow!(
+                "timeout_secs in [retry] must be at least 1 in your config at {:?}",
+                "config.toml"
+            ));
+        }
+        config_builder.retry(retry);
+
+        let fallback = config_file
+            .fallback
+            .iter()
+            .flatten()
+            .map(|fallback| Target {
+                provider: fallback
+                    .provider
+                    .clone()
+                    .unwrap_or_else(|| provider.clone()),
+                model: fallback.model.clone(),
+            })
+            .collect::<Vec<_>>();
+        // `--models openai:gpt-4o,kwaipilot/kat-coder-pro:free` names the
+        // provider before the colon, if there's one with that name
+        let models = args
+            .models
+            .iter()
+            .map(|spec| {
+                let (target_provider, model) = match spec.split_once(':') {
+                    Some((name, model))
+                        if providers.contains_key(&Provider::from(name.to_string())) =>
+                    {
+                        (Provider::from(name.to_string()), model)
+                    }
+                    _ => (provider.clone(), spec.as_str()),
+                };
+                Target {
+                    provider: target_provider,
+                    model: model.to_string(),
+                }
+            })
+            .collect::<Vec<_>>();
+        config_builder.fallback(fallback);
+        config_builder.models(models);
+        config_builder.cache(config_file.cache.unwrap_or_default());
+        config_builder.providers(providers.clone());
+        config_builder.output(args.output);
 
         // CLI args override
         if let Some(model) = &args.model {
             config_builder.model(model);
         }
-        if let Some(persona) = &args.persona {
-            config_builder.persona(*persona);
-        }
-        if let Some(api_key) = &args.api_key {
-            config_builder.api_key(api_key);
-        }
 
         config_builder
             .build()
@@ -88,17 +259,7 @@ mod tests {
     use std::collections::HashMap;
 
     use super::*;
-    use crate::configs::types::ProviderConfig;
-
-    fn create_test_args() -> Args {
-        Args {
-            command: None,
-            model: None,
-            persona: None,
-            api_key: None,
-            args: vec![],
-        }
-    }
+    use crate::configs::types::{FallbackConfig, PersonaConfig, ProviderConfig, SafetyRuleConfig};
 
     fn create_test_config_file() -> ConfigFile {
         ConfigFile {
@@ -108,18 +269,18 @@ mod tests {
                 ProviderConfig {
                     api_key: "test-api-key".to_string(),
                     model: "anthropic/claude-3.5-sonnet".to_string(),
+                    ..Default::default()
                 },
             )])),
-            persona: Some(Persona::Default),
-            auto_copy: false,
-            log_file: None,
+            persona: Some("default".to_string()),
+            ..Default::default()
         }
     }
 
     #[test]
     fn test_from_config_file_success() {
         let config_file = create_test_config_file();
-        let args = create_test_args();
+        let args = Args::default();
 
         let result = Config::from_config_file(&config_file, &args);
         assert!(result.is_ok());
@@ -128,26 +289,43 @@ mod tests {
         assert_eq!(config.provider, Provider::OpenRouter);
         assert_eq!(config.model, "anthropic/claude-3.5-sonnet");
         assert_eq!(config.api_key, "test-api-key");
-        assert_eq!(config.persona, Some(Persona::Default));
+        assert_eq!(config.persona, Persona::default());
         assert!(!config.auto_copy);
     }
 
     #[test]
     fn test_cli_args_override_model() {
         let config_file = create_test_config_file();
-        let mut args = create_test_args();
-        args.model = Some("gpt-4".to_string());
+        let args = Args {
+            model: Some("gpt-4".to_string()),
+            ..Default::default()
+        };
 
         let config = Config::from_config_file(&config_file, &args)
             .expect("config should be created successfully");
         assert_eq!(config.model, "gpt-4");
     }
 
+    #[test]
+    fn test_cli_args_select_json_output() {
+        let config_file = create_test_config_file();
+        let args = Args {
+            output: OutputFormat::Json,
+            ..Default::default()
+        };
+
+        let config = Config::from_config_file(&config_file, &args)
+            .expect("config should be created successfully");
+        assert_eq!(config.output, OutputFormat::Json);
+    }
+
     #[test]
     fn test_cli_args_override_api_key() {
         let config_file = create_test_config_file();
-        let mut args = create_test_args();
-        args.api_key = Some("override-key".to_string());
+        let args = Args {
+            api_key: Some("override-key".to_string()),
+            ..Default::default()
+        };
 
         let config = Config::from_config_file(&config_file, &args)
             .expect("config should be created successfully");
@@ -158,7 +336,7 @@ mod tests {
     fn test_missing_provider_error() {
         let mut config_file = create_test_config_file();
         config_file.provider = None;
-        let args = create_test_args();
+        let args = Args::default();
 
         let result = Config::from_config_file(&config_file, &args);
         assert!(result.is_err());
@@ -172,7 +350,7 @@ mod tests {
         {
             provider_config.api_key = "".to_string();
         }
-        let args = create_test_args();
+        let args = Args::default();
 
         let result = Config::from_config_file(&config_file, &args);
         assert!(result.is_err());
@@ -186,8 +364,10 @@ mod tests {
         {
             provider_config.api_key = "".to_string();
         }
-        let mut args = create_test_args();
-        args.api_key = Some("cli-key".to_string());
+        let args = Args {
+            api_key: Some("cli-key".to_string()),
+            ..Default::default()
+        };
 
         let result = Config::from_config_file(&config_file, &args);
         assert!(result.is_ok());
@@ -204,9 +384,235 @@ mod tests {
             .unwrap()
             .remove(&Provider::OpenAI);
         config_file.provider = Some(Provider::OpenAI);
-        let args = create_test_args();
+        let args = Args::default();
 
         let result = Config::from_config_file(&config_file, &args);
         assert!(result.is_err());
     }
+
+    #[test]
+    fn test_provider_without_api_key_requirement() {
+        let mut config_file = create_test_config_file();
+        config_file.provider = Some(Provider::Ollama);
+        config_file.providers.as_mut().unwrap().insert(
+            Provider::Ollama,
+            ProviderConfig {
+                model: "qwen2.5-coder".to_string(),
+                base_url: Some("http://gpu-box:11434/v1".to_string()),
+                ..Default::default()
+            },
+        );
+        let args = Args::default();
+
+        let config = Config::from_config_file(&config_file, &args)
+            .expect("config should be created without an API key");
+        assert_eq!(config.provider, Provider::Ollama);
+        assert_eq!(config.base_url.as_deref(), Some("http://gpu-box:11434/v1"));
+    }
+
+    #[test]
+    fn test_system_overrides() {
+        let mut config_file = create_test_config_file();
+        config_file.system = Some(SystemConfig {
+            os: Some("linux".to_string()),
+            distribution: None,
+            shell: Some("bash".to_string()),
+        });
+        let args = Args::default();
+
+        let config = Config::from_config_file(&config_file, &args)
+            .expect("config should be created successfully");
+        assert_eq!(config.system.os.as_deref(), Some("linux"));
+        assert_eq!(config.system.shell.as_deref(), Some("bash"));
+    }
+
+    fn create_explain_persona() -> HashMap<String, PersonaConfig> {
+        HashMap::from([(
+            "explain".to_string(),
+            PersonaConfig {
+                system_prompt: "Explain the command step by step".to_string(),
+                model: Some("openai/gpt-4o".to_string()),
+                temperature: Some(0.5),
+            },
+        )])
+    }
+
+    #[test]
+    fn test_user_safety_rules() {
+        let mut config_file = create_test_config_file();
+        config_file.safety_rules = Some(vec![SafetyRuleConfig {
+            pattern: r"kubectl\s+delete".to_string(),
+            message: None,
+        }]);
+
+        let config = Config::from_config_file(&config_file, &Args::default()).unwrap();
+        let warnings = crate::safety::analyze("kubectl delete ns prod", &config.safety_rules);
+        assert_eq!(warnings, [r"Matches 'kubectl\s+delete'"]);
+
+        config_file.safety_rules = Some(vec![SafetyRuleConfig {
+            pattern: "[".to_string(),
+            message: None,
+        }]);
+        assert!(Config::from_config_file(&config_file, &Args::default()).is_err());
+    }
+
+    #[test]
+    fn test_persona_from_config() {
+        let mut config_file = create_test_config_file();
+        config_file.persona = Some("explain".to_string());
+        config_file.personas = Some(create_explain_persona());
+        let args = Args::default();
+
+        let config = Config::from_config_file(&config_file, &args)
+            .expect("config should be created successfully");
+        assert_eq!(config.persona.name, "explain");
+        assert_eq!(config.persona.temperature, Some(0.5));
+        // The persona's model wins over the provider's
+        assert_eq!(config.model, "openai/gpt-4o");
+    }
+
+    #[test]
+    fn test_cli_args_override_persona() {
+        let mut config_file = create_test_config_file();
+        config_file.personas = Some(create_explain_persona());
+        let args = Args {
+            persona: Some("explain".to_string()),
+            model: Some("gpt-4".to_string()),
+            ..Default::default()
+        };
+
+        let config = Config::from_config_file(&config_file, &args)
+            .expect("config should be created successfully");
+        assert_eq!(config.persona.name, "explain");
+        // ...but the CLI model wins over the persona's
+        assert_eq!(config.model, "gpt-4");
+    }
+
+    #[test]
+    fn test_unknown_persona_error() {
+        let config_file = create_test_config_file();
+        let args = Args {
+            persona: Some("pirate".to_string()),
+            ..Default::default()
+        };
+
+        let result = Config::from_config_file(&config_file, &args);
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_zero_timeout_error() {
+        let mut config_file = create_test_config_file();
+        config_file.retry = Some(RetryConfig {
+            timeout_secs: 0,
+            ..Default::default()
+        });
+
+        let err = Config::from_config_file(&config_file, &Args::default()).unwrap_err();
+        assert!(
+            err.to_string()
+                .starts_with("timeout_secs in [retry] must be at least 1")
+        );
+    }
+
+    #[test]
+    fn test_fallback_models() {
+        let mut config_file = create_test_config_file();
+        config_file.fallback = Some(vec![
+            FallbackConfig {
+                provider: None,
+                model: "meta-llama/llama-3.3-70b-instruct:free".to_string(),
+            },
+            FallbackConfig {
+                provider: Some(Provider::Ollama),
+                model: "qwen2.5-coder".to_string(),
+            },
+        ]);
+        config_file.providers.as_mut().unwrap().insert(
+            Provider::Ollama,
+            ProviderConfig {
+                model: "llama3".to_string(),
+                base_url: Some("http://gpu-box:11434/v1".to_string()),
+                ..Default::default()
+            },
+        );
+
+        let config = Config::from_config_file(&config_file, &Args::default()).unwrap();
+        assert_eq!(config.fallback.len(), 2);
+        assert_eq!(config.fallback[0].provider, Provider::OpenRouter);
+        let free = config.with_target(&config.fallback[0]).unwrap();
+        assert_eq!(free.api_key, "test-api-key");
+
+        let ollama = config.with_target(&config.fallback[1]).unwrap();
+        assert_eq!(ollama.provider, Provider::Ollama);
+        assert_eq!(ollama.model, "qwen2.5-coder");
+        assert_eq!(ollama.base_url.as_deref(), Some("http://gpu-box:11434/v1"));
+        assert!(ollama.fallback.is_empty());
+    }
+
+    #[test]
+    fn test_fallback_provider_not_in_providers_map() {
+        let mut config_file = create_test_config_file();
+        config_file.fallback = Some(vec![FallbackConfig {
+            provider: Some(Provider::Anthropic),
+            model: "claude-haiku-4-5".to_string(),
+        }]);
+
+        // Only asking the fallback model fails
+        let config = Config::from_config_file(&config_file, &Args::default()).unwrap();
+        assert!(config.with_target(&config.fallback[0]).is_err());
+    }
+
+    #[test]
+    fn test_fallback_key_is_resolved_when_asked() {
+        let mut config_file = create_test_config_file();
+        config_file.fallback = Some(vec![FallbackConfig {
+            provider: Some(Provider::OpenAI),
+            model: "gpt-4o".to_string(),
+        }]);
+        config_file.providers.as_mut().unwrap().insert(
+            Provider::OpenAI,
+            ProviderConfig {
+                api_key_env: Some("QQ_TEST_UNSET_OPENAI_KEY".to_string()),
+                model: "gpt-5-nano".to_string(),
+                ..Default::default()
+            },
+        );
+
+        let config = Config::from_config_file(&config_file, &Args::default()).unwrap();
+        let err = config.with_target(&config.fallback[0]).unwrap_err();
+        assert!(err.to_string().contains("provider 'openai'"));
+    }
+
+    #[test]
+    fn test_models_to_compare() {
+        let mut config_file = create_test_config_file();
+        config_file.providers.as_mut().unwrap().insert(
+            Provider::OpenAI,
+            ProviderConfig {
+                api_key: "openai-key".to_string(),
+                model: "gpt-5-nano".to_string(),
+                ..Default::default()
+            },
+        );
+        let args = Args {
+            models: vec![
+                "openai:gpt-4o".to_string(),
+                "kwaipilot/kat-coder-pro:free".to_string(),
+            ],
+            ..Default::default()
+        };
+
+        let config = Config::from_config_file(&config_file, &args).unwrap();
+        assert_eq!(config.models.len(), 2);
+        assert_eq!(config.models[0].provider, Provider::OpenAI);
+        assert_eq!(config.models[0].model, "gpt-4o");
+        assert_eq!(
+            config.with_target(&config.models[0]).unwrap().api_key,
+            "openai-key"
+        );
+        // Not a provider name, so the colon is part of the model name
+        assert_eq!(config.models[1].provider, Provider::OpenRouter);
+        assert_eq!(config.models[1].model, "kwaipilot/kat-coder-pro:free");
+    }
 }
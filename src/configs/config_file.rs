@@ -3,15 +3,29 @@ use std::{collections::HashMap, path::PathBuf};
 use anyhow::{Result, anyhow};
 use serde::{Deserialize, Serialize};
 
-use crate::{configs::types::ProviderConfig, persona::Persona, provider::Provider};
+use crate::{
+    configs::types::{
+        CacheConfig, FallbackConfig, ModelPrice, PersonaConfig, ProviderConfig,
+        RedactionRuleConfig, RetryConfig, SafetyRuleConfig, SystemConfig,
+    },
+    provider::Provider,
+};
 
 #[derive(Deserialize, Serialize, Default, Debug)]
 pub struct ConfigFile {
     pub provider: Option<Provider>,
     pub providers: Option<HashMap<Provider, ProviderConfig>>,
-    pub persona: Option<Persona>,
+    pub persona: Option<String>,
+    pub personas: Option<HashMap<String, PersonaConfig>>,
     pub auto_copy: bool,
     pub log_file: Option<PathBuf>,
+    pub system: Option<SystemConfig>,
+    pub safety_rules: Option<Vec<SafetyRuleConfig>>,
+    pub prices: Option<HashMap<String, ModelPrice>>,
+    pub redaction_rules: Option<Vec<RedactionRuleConfig>>,
+    pub retry: Option<RetryConfig>,
+    pub fallback: Option<Vec<FallbackConfig>>,
+    pub cache: Option<CacheConfig>,
 }
 
 impl ConfigFile {
@@ -19,9 +33,9 @@ impl ConfigFile {
         // Verify provider exists in config
         if let Some(ref providers) = self.providers {
             if !providers.contains_key(provider) {
-                let available: Vec<String> = providers.keys().map(|p| format!("{:?}", p)).collect();
+                let available: Vec<String> = providers.keys().map(|p| p.to_string()).collect();
                 return Err(anyhow!(
-                    "Provider '{:?}' not found in config\n\nAvailable providers: {}\nAdd a [providers.{:?}] section to your config",
+                    "Provider '{}' not found in config\n\nAvailable providers: {}\nAdd a [providers.{}] section to your config",
                     provider,
                     available.join(", "),
                     provider
@@ -29,7 +43,7 @@ impl ConfigFile {
             }
         } else {
             return Err(anyhow!(
-                "No providers configured in config\nAdd a [providers.{:?}] section",
+                "No providers configured in config\nAdd a [providers.{}] section",
                 provider
             ));
         }
@@ -50,7 +64,7 @@ impl ConfigFile {
             if let Some(provider_config) = providers.get_mut(&provider) {
                 provider_config.model = String::from(model_name);
             } else {
-                return Err(anyhow!("Provider '{:?}' not found in config", provider,));
+                return Err(anyhow!("Provider '{}' not found in config", provider,));
             }
         } else {
             return Err(anyhow!("No providers configured in config"));
@@ -68,9 +82,13 @@ impl ConfigFile {
         // Update API key for current provider
         if let Some(ref mut providers) = self.providers {
             if let Some(provider_config) = providers.get_mut(&provider) {
+                // Other key sources would take priority over the new key
                 provider_config.api_key = String::from(api_key);
+                provider_config.api_key_env = None;
+                provider_config.api_key_cmd = None;
+                provider_config.api_key_keyring = false;
             } else {
-                return Err(anyhow!("Provider '{:?}' not found in config", provider));
+                return Err(anyhow!("Provider '{}' not found in config", provider));
             }
         } else {
             return Err(anyhow!("No providers configured in config"));
@@ -78,6 +96,27 @@ impl ConfigFile {
 
         Ok(())
     }
+
+    /// Switches the current provider to the key stored in the OS keyring,
+    /// dropping the other key sources, and returns that provider
+    pub fn use_keyring_api_key(&mut self) -> Result<Provider> {
+        let provider = self.provider.clone().ok_or_else(|| {
+            anyhow!("No provider selected in config\nSet 'provider = \"openrouter\"' first")
+        })?;
+
+        let provider_config = self
+            .providers
+            .as_mut()
+            .ok_or_else(|| anyhow!("No providers configured in config"))?
+            .get_mut(&provider)
+            .ok_or_else(|| anyhow!("Provider '{}' not found in config", provider))?;
+        provider_config.api_key.clear();
+        provider_config.api_key_env = None;
+        provider_config.api_key_cmd = None;
+        provider_config.api_key_keyring = true;
+
+        Ok(provider)
+    }
 }
 
 #[cfg(test)]
@@ -87,7 +126,7 @@ mod tests {
 
     use anyhow::Result;
 
-    use super::{ConfigFile, Persona, ProviderConfig};
+    use super::{ConfigFile, ProviderConfig};
     use crate::provider::Provider;
 
     fn create_config_file() -> ConfigFile {
@@ -98,6 +137,7 @@ mod tests {
                     ProviderConfig {
                         api_key: "openrouter-key".to_string(),
                         model: "gpt-4".to_string(),
+                        ..Default::default()
                     },
                 ),
                 (
@@ -105,13 +145,14 @@ mod tests {
                     ProviderConfig {
                         api_key: "openai-key".to_string(),
                         model: "gpt-3.5".to_string(),
+                        ..Default::default()
                     },
                 ),
             ])),
             provider: Some(Provider::OpenRouter),
-            persona: Some(Persona::Default),
+            persona: Some("default".to_string()),
             auto_copy: true,
-            log_file: None,
+            ..Default::default()
         }
     }
 
@@ -146,6 +187,40 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_update_api_key_replaces_other_sources() -> Result<()> {
+        let mut config = create_config_file();
+        let provider_config = config
+            .providers
+            .as_mut()
+            .unwrap()
+            .get_mut(&Provider::OpenRouter)
+            .unwrap();
+        provider_config.api_key_env = Some("OPENROUTER_API_KEY".to_string());
+        provider_config.api_key_cmd = Some("pass show openrouter".to_string());
+
+        config.update_api_key("new-api-key")?;
+        let provider_config = &config.providers.as_ref().unwrap()[&Provider::OpenRouter];
+        assert_eq!(provider_config.api_key_env, None);
+        assert_eq!(provider_config.api_key_cmd, None);
+        Ok(())
+    }
+
+    #[test]
+    fn test_use_keyring_api_key() -> Result<()> {
+        let mut config = create_config_file();
+        assert_eq!(config.use_keyring_api_key()?, Provider::OpenRouter);
+        let provider_config = &config.providers.as_ref().unwrap()[&Provider::OpenRouter];
+        assert!(provider_config.api_key_keyring);
+        assert!(provider_config.api_key.is_empty());
+        assert_eq!(provider_config.api_key_env, None);
+
+        // Setting a plaintext key again stops reading from the keyring
+        config.update_api_key("new-api-key")?;
+        assert!(!config.providers.unwrap()[&Provider::OpenRouter].api_key_keyring);
+        Ok(())
+    }
+
     #[test]
     fn test_update_provider() -> Result<()> {
         let mut config = create_config_file();
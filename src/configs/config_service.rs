@@ -6,9 +6,11 @@ use crate::{
     Args,
     configs::{
         Config,
+        api_key::store_in_keyring,
         config_file::ConfigFile,
-        types::{Environment, FileSystem, RealEnvironment, RealFileSystem},
+        types::{Environment, FileSystem, PersonaConfig, RealEnvironment, RealFileSystem},
     },
+    persona::Persona,
     provider::Provider,
 };
 
@@ -48,10 +50,8 @@ impl<F: FileSystem, E: Environment> ConfigService<F, E> {
     pub fn load(&self, args: &Args) -> Result<Config> {
         let config_path = self.get_config_path();
 
-        if !self.fs.exists(&config_path) {
-            self.create_default_config_file(&config_path)?;
-        }
-        let config_file = self.read_config_file(&config_path)?;
+        let mut config_file = self.read_config_file(&config_path)?;
+        self.load_persona_file(&mut config_file, args)?;
         Config::from_config_file(&config_file, args)
     }
 
@@ -64,7 +64,7 @@ impl<F: FileSystem, E: Environment> ConfigService<F, E> {
             .context(format!("Config file: {:?}", config_path))?;
         self.save_config_file(&config_file, &config_path)?;
 
-        println!("✓ Provider set to '{:?}'", provider);
+        println!("✓ Provider set to '{}'", provider);
         Ok(())
     }
 
@@ -94,20 +94,81 @@ impl<F: FileSystem, E: Environment> ConfigService<F, E> {
         Ok(())
     }
 
-    fn get_config_path(&self) -> PathBuf {
+    /// Stores the API key in the OS keyring instead of the config file
+    pub fn update_api_key_in_keyring(&self, api_key: &str) -> Result<()> {
+        let config_path = self.get_config_path();
+        let mut config_file = self.read_config_file(&config_path)?;
+
+        let provider = config_file
+            .use_keyring_api_key()
+            .context(format!("Config file: {:?}", config_path))?;
+        store_in_keyring(&provider, api_key)?;
+        self.save_config_file(&config_file, &config_path)?;
+
+        println!("✓ API key stored in the keyring");
+        Ok(())
+    }
+
+    /// Directory holding the config file and everything else qq stores
+    pub fn get_home_path(&self) -> PathBuf {
         if let Ok(qq_home) = self.env.var("QQ_HOME_PATH") {
-            let mut path = PathBuf::from(qq_home);
-            path.push("config.toml");
-            return path;
+            return PathBuf::from(qq_home);
         }
 
-        // Fall back to ~/.qq/config.toml
+        // Fall back to ~/.qq
         let mut path = self.env.home_dir().unwrap_or_else(|| PathBuf::from("."));
         path.push(".qq");
-        path.push("config.toml");
         path
     }
 
+    fn get_config_path(&self) -> PathBuf {
+        self.get_home_path().join("config.toml")
+    }
+
+    /// Adds the selected persona from `personas/<name>.md` (or `.txt`) in the
+    /// qq home dir, unless the config or the built-ins already define it
+    fn load_persona_file(&self, config_file: &mut ConfigFile, args: &Args) -> Result<()> {
+        let Some(name) = args.persona.as_ref().or(config_file.persona.as_ref()) else {
+            return Ok(());
+        };
+
+        let defined = config_file
+            .personas
+            .as_ref()
+            .is_some_and(|personas| personas.contains_key(name));
+        // The name becomes part of a file path, so keep it inside the personas dir
+        let is_plain_name = !name.contains(['/', '\\']) && !name.starts_with('.');
+        if defined || Persona::builtin(name).is_some() || !is_plain_name {
+            return Ok(());
+        }
+
+        let personas_dir = self.get_home_path().join("personas");
+        for extension in ["md", "txt"] {
+            let path = personas_dir.join(format!("{}.{}", name, extension));
+            if !self.fs.exists(&path) {
+                continue;
+            }
+
+            let system_prompt = self
+                .fs
+                .read_to_string(&path)
+                .context(format!("Failed to read persona file {:?}", path))?;
+            let persona = PersonaConfig {
+                system_prompt: system_prompt.trim().to_string(),
+                model: None,
+                temperature: None,
+            };
+            let name = name.clone();
+            config_file
+                .personas
+                .get_or_insert_default()
+                .insert(name, persona);
+            break;
+        }
+
+        Ok(())
+    }
+
     fn create_default_config_file(&self, config_path: &Path) -> Result<()> {
         if let Some(parent) = config_path.parent() {
             self.fs.create_dir_all(parent)?;
@@ -119,7 +180,11 @@ impl<F: FileSystem, E: Environment> ConfigService<F, E> {
             .context("Failed to write default config file")
     }
 
+    /// Reads the config file, creating the default one on first use
     fn read_config_file(&self, config_path: &Path) -> Result<ConfigFile> {
+        if !self.fs.exists(config_path) {
+            self.create_default_config_file(config_path)?;
+        }
         self.fs
             .read_to_string(config_path)
             .context("Failed to read config file")
@@ -150,16 +215,6 @@ mod tests {
     use super::*;
     use crate::configs::types::{MockEnvironment, MockFileSystem};
 
-    fn create_test_args() -> Args {
-        Args {
-            command: None,
-            model: None,
-            persona: None,
-            api_key: None,
-            args: vec![],
-        }
-    }
-
     #[test]
     fn test_get_config_path_with_qq_home_env() {
         let mut mock_env = MockEnvironment::new();
@@ -228,7 +283,7 @@ mod tests {
         });
 
         let service = ConfigService::new(mock_fs, mock_env);
-        let args = create_test_args();
+        let args = Args::default();
         let result = service.load(&args);
 
         assert!(result.is_ok());
@@ -278,12 +333,99 @@ mod tests {
         });
 
         let service = ConfigService::new(mock_fs, mock_env);
-        let args = create_test_args();
+        let args = Args::default();
         let result = service.load(&args);
 
         assert!(result.is_ok());
     }
 
+    #[test]
+    fn test_load_ollama_config_without_api_key() {
+        let mut mock_env = MockEnvironment::new();
+        let mut mock_fs = MockFileSystem::new();
+
+        mock_env
+            .expect_var()
+            .returning(|_| Err(anyhow::anyhow!("Not set")));
+
+        mock_env
+            .expect_home_dir()
+            .returning(|| Some(PathBuf::from("/home/user")));
+
+        mock_fs.expect_exists().times(1).returning(|_| true);
+
+        mock_fs.expect_read_to_string().times(1).returning(|_| {
+            Ok(r#"
+    provider = "ollama"
+    persona = "default"
+    auto_copy = false
+
+    [providers.ollama]
+    model = "qwen2.5-coder"
+    base_url = "http://localhost:11434/v1"
+    "#
+            .to_string())
+        });
+
+        let service = ConfigService::new(mock_fs, mock_env);
+        let args = Args::default();
+        let config = service.load(&args).expect("config should load");
+
+        assert_eq!(config.provider, Provider::Ollama);
+        assert_eq!(config.api_key, "");
+        assert_eq!(
+            config.base_url.as_deref(),
+            Some("http://localhost:11434/v1")
+        );
+    }
+
+    #[test]
+    fn test_load_persona_from_file() {
+        let mut mock_env = MockEnvironment::new();
+        let mut mock_fs = MockFileSystem::new();
+
+        mock_env
+            .expect_var()
+            .returning(|_| Ok("/custom/path".to_string()));
+
+        mock_fs.expect_exists().returning(|path| {
+            path == Path::new("/custom/path/config.toml")
+                || path == Path::new("/custom/path/personas/explain.txt")
+        });
+
+        mock_fs
+            .expect_read_to_string()
+            .withf(|path| path == Path::new("/custom/path/config.toml"))
+            .times(1)
+            .returning(|_| {
+                Ok(r#"
+    provider = "openrouter"
+    persona = "explain"
+    auto_copy = false
+
+    [providers.openrouter]
+    api_key = "test-key"
+    model = "anthropic/claude-3.5-sonnet"
+    "#
+                .to_string())
+            });
+        mock_fs
+            .expect_read_to_string()
+            .withf(|path| path == Path::new("/custom/path/personas/explain.txt"))
+            .times(1)
+            .returning(|_| Ok("Explain the command step by step\n".to_string()));
+
+        let service = ConfigService::new(mock_fs, mock_env);
+        let args = Args::default();
+        let config = service.load(&args).expect("config should load");
+
+        assert_eq!(config.persona.name, "explain");
+        assert_eq!(
+            config.persona.system_prompt,
+            "Explain the command step by step"
+        );
+    }
+
     #[test]
     fn test_update_provider() {
         let mut mock_env = MockEnvironment::new();
@@ -297,6 +439,7 @@ mod tests {
             .expect_home_dir()
             .returning(|| Some(PathBuf::from("/home/user")));
 
+        mock_fs.expect_exists().times(1).returning(|_| true);
         mock_fs.expect_read_to_string().times(1).returning(|_| {
             Ok(r#"
     provider = "openrouter"
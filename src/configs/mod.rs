@@ -1,7 +1,12 @@
+mod api_key;
 mod config;
 mod config_file;
 mod config_service;
 mod types;
 
-pub use config::Config;
+pub use config::{Config, Target};
 pub use config_service::ProdConfigService;
+pub use types::{
+    CacheConfig, ModelPrice, PersonaConfig, RedactionRuleConfig, RetryConfig, SafetyRuleConfig,
+    SystemConfig,
+};
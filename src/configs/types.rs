@@ -1,14 +1,123 @@
-use std::path::{Path, PathBuf};
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+};
 
 use anyhow::{Context, Result};
 use serde::{Deserialize, Serialize};
 
-#[derive(Deserialize, Serialize, Debug, Clone)]
+use crate::provider::Provider;
+
+#[derive(Deserialize, Serialize, Debug, Clone, Default)]
 pub struct ProviderConfig {
+    #[serde(default)]
     pub api_key: String,
+    /// Environment variable to read the API key from
+    pub api_key_env: Option<String>,
+    /// Command printing the API key, e.g. `pass show openrouter`
+    pub api_key_cmd: Option<String>,
+    /// Read the API key from the OS keyring, set by `qq use key --keyring`
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub api_key_keyring: bool,
+    pub model: String,
+    pub base_url: Option<String>,
+    pub headers: Option<HashMap<String, String>>,
+}
+
+/// A `[[fallback]]` entry, asked when the models before it fail
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+pub struct FallbackConfig {
+    /// Defaults to the selected provider
+    pub provider: Option<Provider>,
     pub model: String,
 }
 
+/// A persona defined in the `[personas.*]` section of the config
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+pub struct PersonaConfig {
+    pub system_prompt: String,
+    pub model: Option<String>,
+    pub temperature: Option<f32>,
+}
+
+/// Overrides for the detected system, e.g. when working on a remote machine
+#[derive(Deserialize, Serialize, Debug, Clone, Default, PartialEq)]
+pub struct SystemConfig {
+    pub os: Option<String>,
+    pub distribution: Option<String>,
+    pub shell: Option<String>,
+}
+
+/// Price of a model in USD per million tokens, from the `[prices]` section
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq)]
+pub struct ModelPrice {
+    pub prompt: f64,
+    pub completion: f64,
+}
+
+/// Timeout and retries of provider requests, from the `[retry]` section
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq)]
+#[serde(default)]
+pub struct RetryConfig {
+    /// Seconds to wait for the provider to accept the connection, start
+    /// answering or send the next part of the answer
+    pub timeout_secs: u64,
+    /// Attempts after the first one failed with a rate limit, server error or
+    /// timeout
+    pub max_retries: u32,
+    /// Wait before the first retry, doubled for every further one
+    pub backoff_ms: u64,
+    /// Longest wait between attempts, including waits asked for with
+    /// `Retry-After`
+    pub max_backoff_ms: u64,
+}
+
+impl Default for RetryConfig {
+    fn default() -> Self {
+        Self {
+            timeout_secs: 60,
+            max_retries: 2,
+            backoff_ms: 500,
+            max_backoff_ms: 10_000,
+        }
+    }
+}
+
+/// Caching of answers to repeated questions, from the `[cache]` section
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq)]
+#[serde(default)]
+pub struct CacheConfig {
+    pub enabled: bool,
+    /// Seconds an answer is reused for
+    pub ttl_secs: u64,
+    /// Size the cache is trimmed to, dropping the oldest answers first
+    pub max_size_mb: u64,
+}
+
+impl Default for CacheConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            ttl_secs: 7 * 24 * 60 * 60,
+            max_size_mb: 10,
+        }
+    }
+}
+
+/// A user-defined `[[safety_rules]]` entry flagging dangerous commands
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+pub struct SafetyRuleConfig {
+    pub pattern: String,
+    pub message: Option<String>,
+}
+
+/// A user-defined `[[redaction_rules]]` entry for secrets to hide
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+pub struct RedactionRuleConfig {
+    pub name: Option<String>,
+    pub pattern: String,
+}
+
 #[cfg_attr(test, mockall::automock)]
 pub trait FileSystem {
     fn read_to_string(&self, path: &Path) -> Result<String>;
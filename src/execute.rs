@@ -0,0 +1,138 @@
+use std::{env, process::Command};
+
+use anyhow::{Context, Result};
+use rustyline::{DefaultEditor, error::ReadlineError};
+
+use crate::{logging::RequestLogEntryBuilder, query::copy_to_clipboard};
+
+/// What to do with the suggested command
+#[derive(Debug, PartialEq)]
+enum Choice {
+    Run,
+    Edit,
+    Copy,
+    Quit,
+}
+
+impl Choice {
+    fn parse(input: &str) -> Option<Self> {
+        match input.trim().to_lowercase().as_str() {
+            "r" | "run" => Some(Self::Run),
+            "e" | "edit" => Some(Self::Edit),
+            "c" | "copy" => Some(Self::Copy),
+            "q" | "quit" | "" => Some(Self::Quit),
+            _ => None,
+        }
+    }
+}
+
+/// Shows the suggested command and runs it in the user's shell once confirmed,
+/// recording the executed command and its exit code
+pub fn confirm_and_run(response: &str, log_entry: &mut RequestLogEntryBuilder) -> Result<()> {
+    let mut command = strip_code_fence(response);
+    if command.is_empty() {
+        return Ok(());
+    }
+
+    let mut editor = DefaultEditor::new().context("Failed to start line editor")?;
+    loop {
+        let choice = match editor.readline("\x1b[1m[r]un / [e]dit / [c]opy / [q]uit:\x1b[0m ") {
+            Ok(input) => input,
+            Err(ReadlineError::Interrupted | ReadlineError::Eof) => return Ok(()),
+            Err(err) => return Err(err).context("Failed to read input"),
+        };
+
+        match Choice::parse(&choice) {
+            Some(Choice::Run) => break,
+            Some(Choice::Edit) => match editor.readline_with_initial("$ ", (&command, "")) {
+                Ok(edited) if !edited.trim().is_empty() => command = edited.trim().to_string(),
+                Ok(_) | Err(ReadlineError::Interrupted | ReadlineError::Eof) => {}
+                Err(err) => return Err(err).context("Failed to read input"),
+            },
+            Some(Choice::Copy) => {
+                if copy_to_clipboard(&command) {
+                    println!("\x1b[90m(copied)\x1b[0m");
+                }
+                return Ok(());
+            }
+            Some(Choice::Quit) => return Ok(()),
+            None => continue,
+        }
+    }
+
+    let status = shell_command(&command)
+        .status()
+        .context(format!("Failed to run '{}'", command))?;
+    log_entry.executed_command(command);
+    log_entry.exit_code(status.code());
+    Ok(())
+}
+
+/// Takes the answer out of a fenced code block if the model added one
+pub fn strip_code_fence(response: &str) -> String {
+    let response = response.trim();
+    let Some(fenced) = response.strip_prefix("```") else {
+        return response.to_string();
+    };
+
+    // Skip the language tag on the opening fence
+    let body = fenced.split_once('\n').map_or("", |(_, body)| body);
+    body.trim_end()
+        .strip_suffix("```")
+        .unwrap_or(body)
+        .trim()
+        .to_string()
+}
+
+/// Runs a command line in the user's shell
+#[cfg(unix)]
+pub fn shell_command(command: &str) -> Command {
+    let shell = env::var("SHELL")
+        .ok()
+        .filter(|shell| !shell.is_empty())
+        .unwrap_or_else(|| String::from("/bin/sh"));
+    let mut process = Command::new(shell);
+    process.arg("-c").arg(command);
+    process
+}
+
+#[cfg(not(unix))]
+pub fn shell_command(command: &str) -> Command {
+    let shell = env::var("COMSPEC").unwrap_or_else(|_| String::from("cmd.exe"));
+    let mut process = Command::new(shell);
+    process.arg("/C").arg(command);
+    process
+}
+
+#[cfg(test)]
+#[cfg_attr(coverage_nightly, coverage(off))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_choice() {
+        assert_eq!(Choice::parse("r"), Some(Choice::Run));
+        assert_eq!(Choice::parse(" Edit "), Some(Choice::Edit));
+        assert_eq!(Choice::parse("c"), Some(Choice::Copy));
+        assert_eq!(Choice::parse(""), Some(Choice::Quit));
+        assert_eq!(Choice::parse("x"), None);
+    }
+
+    #[test]
+    fn test_strip_code_fence_without_fence() {
+        assert_eq!(strip_code_fence("ls -la\n"), "ls -la");
+    }
+
+    #[test]
+    fn test_strip_code_fence() {
+        assert_eq!(strip_code_fence("```bash\ndu -sh *\n```"), "du -sh *");
+        assert_eq!(strip_code_fence("```\ngit status\n```\n"), "git status");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_shell_command_exit_code() {
+        let status = shell_command("exit 3").status().unwrap();
+        assert_eq!(status.code(), Some(3));
+    }
+}
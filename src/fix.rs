@@ -0,0 +1,138 @@
+use std::env;
+
+use anyhow::{Result, anyhow};
+
+use crate::input::read_stdin;
+
+/// Set by the shell integration after every command
+const LAST_COMMAND_VAR: &str = "QQ_LAST_COMMAND";
+const LAST_STATUS_VAR: &str = "QQ_LAST_STATUS";
+
+/// A command that didn't do what the user wanted, with whatever it printed
+#[derive(Debug, PartialEq)]
+pub struct FailedCommand {
+    pub command: String,
+    pub exit_status: Option<i32>,
+    pub stderr: Option<String>,
+}
+
+impl FailedCommand {
+    /// Uses the explicit `--cmd` and `--stderr` if given, falling back to the
+    /// shell integration and piped error output
+    pub fn detect(command: Option<&str>, stderr: Option<&str>) -> Result<Self> {
+        let stderr = match stderr {
+            Some(stderr) => Some(stderr.to_string()),
+            None => read_stdin()?,
+        };
+        Self::from_sources(command, stderr, |var| env::var(var).ok())
+    }
+
+    fn from_sources(
+        command: Option<&str>,
+        stderr: Option<String>,
+        env: impl Fn(&str) -> Option<String>,
+    ) -> Result<Self> {
+        // The recorded status only belongs to the recorded command
+        let (command, exit_status) = match command {
+            Some(command) => (Some(command.to_string()), None),
+            None => (
+                env(LAST_COMMAND_VAR),
+                env(LAST_STATUS_VAR).and_then(|status| status.trim().parse().ok()),
+            ),
+        };
+
+        let command = command
+            .filter(|command| !command.trim().is_empty())
+            .ok_or_else(|| {
+                anyhow!(
+                    "No previous command found\n\nInstall the shell integration with: eval \"$(qq shell-init zsh)\"\nOr pass the command with: qq fix --cmd \"...\""
+                )
+            })?;
+
+        Ok(Self {
+            command,
+            exit_status,
+            stderr: stderr.filter(|stderr| !stderr.trim().is_empty()),
+        })
+    }
+
+    pub fn to_prompt(&self) -> String {
+        let mut prompt = format!("This command failed:\n{}", self.command);
+        if let Some(status) = self.exit_status {
+            prompt.push_str(&format!("\n\nExit status: {}", status));
+        }
+        if let Some(stderr) = &self.stderr {
+            prompt.push_str(&format!(
+                "\n\n--- Error output ---\n{}\n--- End of error output ---",
+                stderr.trim_end()
+            ));
+        }
+        prompt
+    }
+}
+
+#[cfg(test)]
+#[cfg_attr(coverage_nightly, coverage(off))]
+mod tests {
+    use super::*;
+
+    fn shell_env(var: &str) -> Option<String> {
+        match var {
+            LAST_COMMAND_VAR => Some("git pussh".to_string()),
+            LAST_STATUS_VAR => Some("1".to_string()),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn test_from_shell_integration() {
+        let failed = FailedCommand::from_sources(None, None, shell_env).unwrap();
+        assert_eq!(
+            failed,
+            FailedCommand {
+                command: "git pussh".to_string(),
+                exit_status: Some(1),
+                stderr: None,
+            }
+        );
+    }
+
+    #[test]
+    fn test_explicit_command_wins() {
+        let failed = FailedCommand::from_sources(
+            Some("cargo biuld"),
+            Some("error: no such command: `biuld`\n".to_string()),
+            shell_env,
+        )
+        .unwrap();
+
+        assert_eq!(failed.command, "cargo biuld");
+        assert_eq!(failed.exit_status, None);
+        assert_eq!(
+            failed.stderr.as_deref(),
+            Some("error: no such command: `biuld`\n")
+        );
+    }
+
+    #[test]
+    fn test_without_command() {
+        assert!(FailedCommand::from_sources(None, None, |_| None).is_err());
+        assert!(FailedCommand::from_sources(Some(" "), None, |_| None).is_err());
+    }
+
+    #[test]
+    fn test_to_prompt() {
+        let failed = FailedCommand {
+            command: "tar -xzf archive.zip".to_string(),
+            exit_status: Some(2),
+            stderr: Some("gzip: stdin has more than one entry\n".to_string()),
+        };
+
+        assert_eq!(
+            failed.to_prompt(),
+            "This command failed:\ntar -xzf archive.zip\n\n\
+             Exit status: 2\n\n\
+             --- Error output ---\ngzip: stdin has more than one entry\n--- End of error output ---"
+        );
+    }
+}
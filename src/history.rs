@@ -0,0 +1,313 @@
+use std::path::PathBuf;
+
+use anyhow::{Result, anyhow};
+use chrono::{DateTime, NaiveDate};
+
+use crate::{
+    args::HistoryAction,
+    logging::{RequestLogEntry, read_log},
+    query::copy_to_clipboard,
+};
+
+/// Longest prompt shown in the history list
+const MAX_PROMPT_CHARS: usize = 60;
+
+/// Criteria for the requests listed by `qq history`
+#[derive(Debug, Default)]
+pub struct HistoryFilter {
+    pub model: Option<String>,
+    pub since: Option<NaiveDate>,
+    pub until: Option<NaiveDate>,
+    pub errors_only: bool,
+    pub search: Option<String>,
+}
+
+impl HistoryFilter {
+    pub fn matches(&self, entry: &RequestLogEntry) -> bool {
+        if let Some(model) = &self.model
+            && !entry.config.model.contains(model.as_str())
+        {
+            return false;
+        }
+
+        if self.since.is_some() || self.until.is_some() {
+            let Some(date) = entry_date(entry) else {
+                return false;
+            };
+            if self.since.is_some_and(|since| date < since)
+                || self.until.is_some_and(|until| date > until)
+            {
+                return false;
+            }
+        }
+
+        if self.errors_only && entry.error.is_empty() {
+            return false;
+        }
+
+        if let Some(search) = &self.search {
+            let search = search.to_lowercase();
+            return entry.user_prompt.to_lowercase().contains(&search)
+                || entry.response.to_lowercase().contains(&search);
+        }
+
+        true
+    }
+}
+
+/// Request log entries numbered from the most recent, which is 1
+struct History {
+    entries: Vec<RequestLogEntry>,
+}
+
+impl History {
+    fn load(log_file: Option<&PathBuf>) -> Result<Self> {
+        Ok(Self {
+            entries: read_log(log_file)?,
+        })
+    }
+
+    /// Returns up to `limit` of the most recent matching entries, oldest first
+    fn list(&self, filter: &HistoryFilter, limit: usize) -> Vec<(usize, &RequestLogEntry)> {
+        let mut entries: Vec<_> = self
+            .numbered()
+            .filter(|(_, entry)| filter.matches(entry))
+            .take(limit)
+            .collect();
+        entries.reverse();
+        entries
+    }
+
+    fn get(&self, number: usize) -> Result<&RequestLogEntry> {
+        self.numbered()
+            .find(|(n, _)| *n == number)
+            .map(|(_, entry)| entry)
+            .ok_or_else(|| anyhow!("No request #{} in the history", number))
+    }
+
+    fn numbered(&self) -> impl Iterator<Item = (usize, &RequestLogEntry)> {
+        self.entries
+            .iter()
+            .rev()
+            .enumerate()
+            .map(|(i, entry)| (i + 1, entry))
+    }
+}
+
+/// Lists the matching requests, or shows or copies a single one
+pub fn show_history(
+    log_file: Option<&PathBuf>,
+    action: Option<&HistoryAction>,
+    filter: &HistoryFilter,
+    limit: usize,
+) -> Result<()> {
+    let history = History::load(log_file)?;
+    match action {
+        None => {
+            print_list(&history, filter, limit);
+            Ok(())
+        }
+        Some(HistoryAction::Show { number }) => print_entry(&history, *number),
+        Some(HistoryAction::Copy { number }) => copy_entry(&history, *number),
+    }
+}
+
+fn print_list(history: &History, filter: &HistoryFilter, limit: usize) {
+    for (number, entry) in history.list(filter, limit) {
+        let time = DateTime::parse_from_rfc3339(&entry.time)
+            .map(|time| time.format("%Y-%m-%d %H:%M").to_string())
+            .unwrap_or_else(|_| entry.time.clone());
+        let status = if entry.error.is_empty() {
+            ""
+        } else {
+            " \x1b[31m[error]\x1b[0m"
+        };
+
+        println!(
+            "\x1b[90m{:>4}  {}  {}\x1b[0m  {}{}",
+            number,
+            time,
+            entry.config.model,
+            summarize(&entry.user_prompt),
+            status
+        );
+    }
+}
+
+/// Prints the response of a past request, or its error if it failed
+fn print_entry(history: &History, number: usize) -> Result<()> {
+    let entry = history.get(number)?;
+    if entry.error.is_empty() {
+        println!("{}", entry.response);
+    } else {
+        println!("{}", entry.error);
+    }
+    Ok(())
+}
+
+fn copy_entry(history: &History, number: usize) -> Result<()> {
+    let entry = history.get(number)?;
+    if entry.response.is_empty() {
+        return Err(anyhow!("Request #{} has no response to copy", number));
+    }
+    if !copy_to_clipboard(&entry.response) {
+        return Err(anyhow!("Failed to copy to the clipboard"));
+    }
+    println!("\x1b[90m(copied)\x1b[0m");
+    Ok(())
+}
+
+fn entry_date(entry: &RequestLogEntry) -> Option<NaiveDate> {
+    DateTime::parse_from_rfc3339(&entry.time)
+        .ok()
+        .map(|time| time.date_naive())
+}
+
+/// First line of the prompt, shortened to fit on one line
+fn summarize(prompt: &str) -> String {
+    let line = prompt.lines().next().unwrap_or_default();
+    if line.chars().count() <= MAX_PROMPT_CHARS {
+        return line.to_string();
+    }
+    let mut summary: String = line.chars().take(MAX_PROMPT_CHARS - 3).collect();
+    summary.push_str("...");
+    summary
+}
+
+#[cfg(test)]
+#[cfg_attr(coverage_nightly, coverage(off))]
+mod tests {
+    use super::*;
+    use crate::logging::ConfigForLogging;
+
+    fn entry(time: &str, model: &str, prompt: &str, error: &str) -> RequestLogEntry {
+        RequestLogEntry {
+            time: time.to_string(),
+            config: ConfigForLogging {
+                provider: "openrouter".to_string(),
+                model: model.to_string(),
+                persona: Some("default".to_string()),
+                auto_copy: false,
+            },
+            user_prompt: prompt.to_string(),
+            attached_files: vec![],
+            response: format!("answer to {prompt}"),
+            error: error.to_string(),
+            llm_response_time_ms: 0,
+            cached: false,
+            retries: 0,
+            failed_attempts: vec![],
+            usage: None,
+            cost_usd: None,
+            safety_warnings: vec![],
+            executed_command: None,
+            exit_code: None,
+            total_runtime_ms: 0,
+        }
+    }
+
+    fn history() -> History {
+        History {
+            entries: vec![
+                entry(
+                    "2025-01-18T09:00:00+01:00",
+                    "openai/gpt-4o",
+                    "list files",
+                    "",
+                ),
+                entry(
+                    "2025-01-19T10:00:00+01:00",
+                    "anthropic/claude-3.5-sonnet",
+                    "find large files",
+                    "",
+                ),
+                entry(
+                    "2025-01-20T11:00:00+01:00",
+                    "openai/gpt-4o",
+                    "undo last commit",
+                    "rate limited",
+                ),
+            ],
+        }
+    }
+
+    fn prompts(entries: Vec<(usize, &RequestLogEntry)>) -> Vec<(usize, &str)> {
+        entries
+            .into_iter()
+            .map(|(number, entry)| (number, entry.user_prompt.as_str()))
+            .collect()
+    }
+
+    #[test]
+    fn test_list_numbers_from_most_recent() {
+        let history = history();
+        assert_eq!(
+            prompts(history.list(&HistoryFilter::default(), 2)),
+            [(2, "find large files"), (1, "undo last commit")]
+        );
+    }
+
+    #[test]
+    fn test_filter_by_model_and_errors() {
+        let history = history();
+        let filter = HistoryFilter {
+            model: Some("gpt-4o".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(
+            prompts(history.list(&filter, 10)),
+            [(3, "list files"), (1, "undo last commit")]
+        );
+
+        let filter = HistoryFilter {
+            errors_only: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            prompts(history.list(&filter, 10)),
+            [(1, "undo last commit")]
+        );
+    }
+
+    #[test]
+    fn test_filter_by_date_range() {
+        let history = history();
+        let filter = HistoryFilter {
+            since: NaiveDate::from_ymd_opt(2025, 1, 19),
+            until: NaiveDate::from_ymd_opt(2025, 1, 19),
+            ..Default::default()
+        };
+        assert_eq!(
+            prompts(history.list(&filter, 10)),
+            [(2, "find large files")]
+        );
+    }
+
+    #[test]
+    fn test_filter_by_text() {
+        let history = history();
+        let filter = HistoryFilter {
+            search: Some("LARGE".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(
+            prompts(history.list(&filter, 10)),
+            [(2, "find large files")]
+        );
+    }
+
+    #[test]
+    fn test_get() {
+        let history = history();
+        assert_eq!(history.get(3).unwrap().user_prompt, "list files");
+        assert!(history.get(4).is_err());
+        assert!(history.get(0).is_err());
+    }
+
+    #[test]
+    fn test_summarize() {
+        assert_eq!(summarize("short\nsecond line"), "short");
+        let long = "x".repeat(100);
+        assert_eq!(summarize(&long).chars().count(), MAX_PROMPT_CHARS);
+    }
+}
@@ -0,0 +1,233 @@
+use std::{
+    fs::File,
+    io::{self, IsTerminal, Read},
+    path::{Path, PathBuf},
+};
+
+use anyhow::{Context, Result, anyhow};
+
+/// Maximum amount of piped data attached to a prompt
+const MAX_STDIN_BYTES: usize = 64 * 1024;
+/// Maximum amount of data attached from a single file
+const MAX_FILE_BYTES: usize = 64 * 1024;
+
+const TRUNCATED_MARKER: &str = "\n[... input truncated ...]";
+
+/// A file attached to the prompt with `--file`
+pub struct Attachment {
+    pub path: PathBuf,
+    pub contents: String,
+}
+
+/// Reads data piped into qq, if stdin is not a terminal
+pub fn read_stdin() -> Result<Option<String>> {
+    let stdin = io::stdin();
+    if stdin.is_terminal() {
+        return Ok(None);
+    }
+
+    let (data, truncated) =
+        read_capped(stdin.lock(), MAX_STDIN_BYTES).context("Failed to read stdin")?;
+    let data = to_text(&data, truncated);
+    if data.trim().is_empty() {
+        return Ok(None);
+    }
+    Ok(Some(data))
+}
+
+/// Reads every file matching the given paths or glob patterns
+pub fn read_files(patterns: &[String]) -> Result<Vec<Attachment>> {
+    let mut paths: Vec<PathBuf> = Vec::new();
+    for pattern in patterns {
+        for path in expand_pattern(pattern)? {
+            if !paths.contains(&path) {
+                paths.push(path);
+            }
+        }
+    }
+
+    paths.iter().map(|path| read_attachment(path)).collect()
+}
+
+/// Combines the question from the command line with attached files and piped
+/// context. Without a question or files the piped data becomes the whole
+/// prompt.
+pub fn build_user_prompt(question: &str, context: Option<&str>, files: &[Attachment]) -> String {
+    let mut sections = Vec::new();
+    if !question.trim().is_empty() {
+        sections.push(question.to_string());
+    }
+
+    for file in files {
+        sections.push(format!(
+            "--- File: {} ---\n{}\n--- End of file ---",
+            file.path.display(),
+            file.contents.trim_end()
+        ));
+    }
+
+    if let Some(context) = context {
+        if sections.is_empty() {
+            return context.to_string();
+        }
+        sections.push(format!(
+            "--- Context (stdin) ---\n{}\n--- End of context ---",
+            context.trim_end()
+        ));
+    }
+
+    sections.join("\n\n")
+}
+
+fn expand_pattern(pattern: &str) -> Result<Vec<PathBuf>> {
+    let is_glob = pattern.contains(['*', '?', '[']);
+    if !is_glob {
+        return Ok(vec![PathBuf::from(pattern)]);
+    }
+
+    let paths = glob::glob(pattern)
+        .context(format!("Invalid glob pattern '{}'", pattern))?
+        .filter_map(|entry| entry.ok())
+        .filter(|path| path.is_file())
+        .collect::<Vec<_>>();
+
+    if paths.is_empty() {
+        return Err(anyhow!("No files match '{}'", pattern));
+    }
+    Ok(paths)
+}
+
+fn read_attachment(path: &Path) -> Result<Attachment> {
+    let file = File::open(path).context(format!("Failed to open file {:?}", path))?;
+    let (data, truncated) =
+        read_capped(file, MAX_FILE_BYTES).context(format!("Failed to read file {:?}", path))?;
+
+    if is_binary(&data) {
+        return Err(anyhow!("Refusing to attach binary file {:?}", path));
+    }
+
+    Ok(Attachment {
+        path: path.to_path_buf(),
+        contents: to_text(&data, truncated),
+    })
+}
+
+/// Reads at most `max_bytes` from `reader`, reporting whether there was more
+fn read_capped(reader: impl Read, max_bytes: usize) -> io::Result<(Vec<u8>, bool)> {
+    let mut buffer = Vec::new();
+    reader.take(max_bytes as u64 + 1).read_to_end(&mut buffer)?;
+
+    let truncated = buffer.len() > max_bytes;
+    buffer.truncate(max_bytes);
+    Ok((buffer, truncated))
+}
+
+fn to_text(data: &[u8], truncated: bool) -> String {
+    // The cap may split a multi-byte character, which becomes U+FFFD
+    let mut text = String::from_utf8_lossy(data).into_owned();
+    if truncated {
+        text.push_str(TRUNCATED_MARKER);
+    }
+    text
+}
+
+/// Same heuristic as git: text files don't contain NUL bytes
+fn is_binary(data: &[u8]) -> bool {
+    data.contains(&0)
+}
+
+#[cfg(test)]
+#[cfg_attr(coverage_nightly, coverage(off))]
+mod tests {
+    use super::*;
+
+    fn attachment(path: &str, contents: &str) -> Attachment {
+        Attachment {
+            path: PathBuf::from(path),
+            contents: contents.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_build_user_prompt_without_context() {
+        assert_eq!(build_user_prompt("list files", None, &[]), "list files");
+    }
+
+    #[test]
+    fn test_build_user_prompt_with_context() {
+        let prompt = build_user_prompt("why is this failing", Some("error: E0425\n"), &[]);
+        assert_eq!(
+            prompt,
+            "why is this failing\n\n--- Context (stdin) ---\nerror: E0425\n--- End of context ---"
+        );
+    }
+
+    #[test]
+    fn test_build_user_prompt_context_only() {
+        assert_eq!(
+            build_user_prompt("", Some("explain this regex: ^a+$"), &[]),
+            "explain this regex: ^a+$"
+        );
+    }
+
+    #[test]
+    fn test_build_user_prompt_with_files() {
+        let files = [
+            attachment("src/main.rs", "fn main() {}\n"),
+            attachment("Cargo.toml", "[package]"),
+        ];
+        let prompt = build_user_prompt("why does this not compile", None, &files);
+        assert_eq!(
+            prompt,
+            "why does this not compile\n\n\
+             --- File: src/main.rs ---\nfn main() {}\n--- End of file ---\n\n\
+             --- File: Cargo.toml ---\n[package]\n--- End of file ---"
+        );
+    }
+
+    #[test]
+    fn test_build_user_prompt_files_and_context_without_question() {
+        let files = [attachment("run.sh", "make test")];
+        let prompt = build_user_prompt("", Some("exit 2"), &files);
+        assert_eq!(
+            prompt,
+            "--- File: run.sh ---\nmake test\n--- End of file ---\n\n\
+             --- Context (stdin) ---\nexit 2\n--- End of context ---"
+        );
+    }
+
+    #[test]
+    fn test_read_capped_within_limit() {
+        let (data, truncated) = read_capped("short input".as_bytes(), 64).unwrap();
+        assert_eq!(data, b"short input");
+        assert!(!truncated);
+    }
+
+    #[test]
+    fn test_read_capped_truncates() {
+        let (data, truncated) = read_capped("0123456789".as_bytes(), 4).unwrap();
+        assert_eq!(to_text(&data, truncated), format!("0123{TRUNCATED_MARKER}"));
+    }
+
+    #[test]
+    fn test_is_binary() {
+        assert!(is_binary(b"\x7fELF\x02\x01\x01\x00"));
+        assert!(!is_binary("plain text ✓".as_bytes()));
+    }
+
+    #[test]
+    fn test_read_files_refuses_binary() {
+        let path = std::env::temp_dir().join(format!("qq-binary-{}.bin", std::process::id()));
+        std::fs::write(&path, b"\x00\x01\x02").unwrap();
+
+        let result = read_files(&[path.to_string_lossy().into_owned()]);
+        std::fs::remove_file(&path).unwrap();
+
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_expand_pattern_without_matches() {
+        assert!(expand_pattern("/nonexistent-qq-dir/*.rs").is_err());
+    }
+}
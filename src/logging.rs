@@ -1,27 +1,76 @@
-use std::{fs::OpenOptions, io::Write, path::PathBuf};
+use std::{
+    fs::{self, OpenOptions},
+    io::Write,
+    path::PathBuf,
+    time::Instant,
+};
 
-use anyhow::Result;
+use anyhow::{Context, Result, anyhow};
 use derive_builder::Builder;
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 
-use crate::{configs::Config, persona::Persona};
+use crate::{configs::Config, provider::TokenUsage};
 
-#[derive(Serialize, Builder, Clone)]
+#[derive(Serialize, Deserialize, Builder, Clone, Debug)]
 #[builder(setter(into))]
 pub struct RequestLogEntry {
     pub time: String,
     pub config: ConfigForLogging,
     #[builder(default)]
+    #[serde(default)]
     pub user_prompt: String,
     #[builder(default)]
+    #[serde(default)]
+    pub attached_files: Vec<PathBuf>,
+    #[builder(default)]
+    #[serde(default)]
     pub response: String,
     #[builder(default)]
+    #[serde(default)]
     pub error: String,
     #[builder(default)]
+    #[serde(default)]
     pub llm_response_time_ms: u64,
+    /// Answered from the response cache, without asking the model
+    #[builder(default)]
+    #[serde(default)]
+    pub cached: bool,
+    /// Attempts repeated after rate limits, server errors or timeouts
+    #[builder(default)]
+    #[serde(default)]
+    pub retries: u32,
+    /// Models that failed before the one that answered, with `[[fallback]]`
+    #[builder(default)]
+    #[serde(default)]
+    pub failed_attempts: Vec<FailedAttempt>,
+    #[builder(default)]
+    #[serde(default)]
+    pub usage: Option<TokenUsage>,
+    /// Reported by the provider or estimated from the price table
+    #[builder(default)]
+    #[serde(default)]
+    pub cost_usd: Option<f64>,
+    #[builder(default)]
+    #[serde(default)]
+    pub safety_warnings: Vec<String>,
+    /// Command run with `--execute`, if the user confirmed it
+    #[builder(default)]
+    #[serde(default)]
+    pub executed_command: Option<String>,
+    #[builder(default)]
+    #[serde(default)]
+    pub exit_code: Option<i32>,
     pub total_runtime_ms: u64,
 }
 
+/// A model that failed to answer a request
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct FailedAttempt {
+    pub provider: String,
+    pub model: String,
+    pub error: String,
+}
+
 impl RequestLogEntry {
     pub fn write_to_file(&self, log_file: &PathBuf) -> Result<()> {
         let mut file = OpenOptions::new()
@@ -32,23 +81,112 @@ impl RequestLogEntry {
         writeln!(file, "{}", json)?;
         Ok(())
     }
+
+    /// Reads every entry of the log, oldest first. Lines that can't be parsed,
+    /// e.g. from an interrupted write, are skipped.
+    pub fn read_from_file(log_file: &PathBuf) -> Result<Vec<Self>> {
+        let contents =
+            fs::read_to_string(log_file).context(format!("Failed to read log {:?}", log_file))?;
+        Ok(contents
+            .lines()
+            .filter_map(|line| serde_json::from_str(line).ok())
+            .collect())
+    }
+}
+
+/// Reads the configured request log, which is empty until the first request
+pub fn read_log(log_file: Option<&PathBuf>) -> Result<Vec<RequestLogEntry>> {
+    let log_file = log_file.ok_or_else(|| {
+        anyhow!("Request logging is off\n\nSet 'log_file' in your config to keep a history")
+    })?;
+
+    if !log_file.exists() {
+        return Ok(Vec::new());
+    }
+    RequestLogEntry::read_from_file(log_file)
 }
 
-#[derive(Serialize, Clone)]
+/// Finishes the entry and appends it to the log file, if one is configured.
+/// Returns the finished entry.
+pub fn write_log_entry(
+    log_entry: &mut RequestLogEntryBuilder,
+    total_start: Instant,
+    log_file: Option<&PathBuf>,
+) -> Option<RequestLogEntry> {
+    let total_duration = total_start.elapsed();
+    log_entry.total_runtime_ms(total_duration.as_millis() as u64);
+
+    match log_entry.build() {
+        Ok(log) => {
+            if let Some(log_file) = log_file
+                && let Err(err) = log.write_to_file(log_file)
+            {
+                eprintln!("{}", err);
+            }
+            Some(log)
+        }
+        Err(err) => {
+            eprintln!("{}", err);
+            None
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct ConfigForLogging {
     pub provider: String,
     pub model: String,
-    pub persona: Option<Persona>,
+    /// Logs written before personas could be named have `null` here
+    #[serde(default)]
+    pub persona: Option<String>,
     pub auto_copy: bool,
 }
 
 impl From<&Config> for ConfigForLogging {
     fn from(config: &Config) -> Self {
         Self {
-            provider: format!("{:?}", config.provider),
+            provider: config.provider.to_string(),
             model: config.model.clone(),
-            persona: config.persona,
+            persona: Some(config.persona.name.clone()),
             auto_copy: config.auto_copy,
         }
     }
 }
+
+#[cfg(test)]
+#[cfg_attr(coverage_nightly, coverage(off))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_read_from_file() {
+        let path = std::env::temp_dir().join(format!("qq-log-{}.jsonl", std::process::id()));
+        let line = r#"{"time":"2025-01-19T10:30:00-08:00","config":{"provider":"openrouter","model":"anthropic/claude-3.5-sonnet","auto_copy":true},"user_prompt":"how to list files","response":"ls -la","llm_response_time_ms":450,"total_runtime_ms":502}"#;
+        fs::write(&path, format!("{line}\n{{\"time\":\n{line}\n")).unwrap();
+
+        let entries = RequestLogEntry::read_from_file(&path);
+        fs::remove_file(&path).unwrap();
+
+        let entries = entries.unwrap();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].response, "ls -la");
+        assert_eq!(entries[0].config.persona, None);
+        assert!(entries[0].safety_warnings.is_empty());
+    }
+
+    #[test]
+    fn test_read_baseline_entry() {
+        let path =
+            std::env::temp_dir().join(format!("qq-log-baseline-{}.jsonl", std::process::id()));
+        let line = r#"{"time":"2025-01-19T10:30:00-08:00","config":{"provider":"OpenRouter","model":"anthropic/claude-3.5-sonnet","persona":null,"auto_copy":true},"user_prompt":"how to list files","response":"ls -la","error":"","llm_response_time_ms":450,"total_runtime_ms":502}"#;
+        fs::write(&path, format!("{line}\n")).unwrap();
+
+        let entries = RequestLogEntry::read_from_file(&path);
+        fs::remove_file(&path).unwrap();
+
+        let entries = entries.unwrap();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].config.persona, None);
+        assert_eq!(entries[0].user_prompt, "how to list files");
+    }
+}
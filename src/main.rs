@@ -1,35 +1,110 @@
 #![cfg_attr(coverage_nightly, feature(coverage_attribute))]
 
 mod args;
+mod cache;
+mod commit;
+mod compare;
 mod configs;
+mod execute;
+mod fix;
+mod history;
+mod input;
 mod logging;
+mod models;
 mod persona;
+mod pricing;
 mod prompts;
 mod provider;
 mod providers;
+mod query;
+mod redact;
+mod repl;
+mod retry;
+mod safety;
+mod session;
+mod shell_init;
+mod stats;
+mod system_info;
 
-use std::time::Instant;
+use std::{
+    io::{self, IsTerminal},
+    path::PathBuf,
+    time::Instant,
+};
 
-use arboard::Clipboard;
 use chrono::Local;
 use clap::Parser;
-use spinoff::{Color, Spinner, spinners};
 
 use crate::{
-    args::{Args, Commands, UseTarget},
+    args::{Args, CacheAction, Commands, UseTarget},
+    cache::ResponseCache,
+    commit::{build_commit_prompt, confirm_and_commit, staged_diff},
+    compare::compare_models,
     configs::{Config, ProdConfigService},
-    logging::RequestLogEntryBuilder,
-    persona::Persona,
-    prompts::get_system_prompt,
-    provider::{LLMProvider, Provider},
-    providers::{OpenAI, OpenRouter},
+    execute::confirm_and_run,
+    fix::FailedCommand,
+    history::{HistoryFilter, show_history},
+    input::{build_user_prompt, read_files, read_stdin},
+    logging::{FailedAttempt, RequestLogEntryBuilder, write_log_entry},
+    models::{ModelFilter, check_model, list_models, show_models},
+    persona::{COMMIT_PERSONA, FIX_PERSONA},
+    provider::Message,
+    providers::create_provider,
+    query::{OutputFormat, ask, redact_prompt},
+    repl::Repl,
+    retry::is_retryable,
+    session::{SessionStore, session_key},
+    shell_init::widget_script,
+    stats::show_stats,
 };
 
 #[tokio::main]
 async fn main() {
-    let args = Args::parse();
+    let mut args = Args::parse();
+
+    // The widget script doesn't depend on the config, so it works before setup
+    if let Some(Commands::ShellInit { shell }) = &args.command {
+        print!("{}", widget_script(*shell));
+        return;
+    }
+
+    // `qq fix` and `qq commit` answer with their own persona unless another
+    // one is picked
+    if args.persona.is_none() {
+        match args.command {
+            Some(Commands::Fix { .. }) => args.persona = Some(FIX_PERSONA.to_string()),
+            Some(Commands::Commit) => args.persona = Some(COMMIT_PERSONA.to_string()),
+            _ => {}
+        }
+    }
 
+    // Setting things up must work while the config is still incomplete
     let config_service = ProdConfigService::default();
+    if let Some(Commands::Use { target }) = &args.command {
+        let result = match target {
+            UseTarget::Provider { name } => config_service.update_provider(name),
+            UseTarget::Model { name, force } => {
+                let checked = if *force {
+                    Ok(())
+                } else {
+                    validate_model(&args, &config_service, name).await
+                };
+                checked.and_then(|()| config_service.update_model(name))
+            }
+            UseTarget::Key { key, keyring: true } => config_service.update_api_key_in_keyring(key),
+            UseTarget::Key {
+                key,
+                keyring: false,
+            } => config_service.update_api_key(key),
+        };
+
+        if let Err(err) = result {
+            eprintln!("Error: {}", err);
+            std::process::exit(1);
+        }
+        return;
+    }
+
     let config = match config_service.load(&args) {
         Ok(config) => config,
         Err(err) => {
@@ -38,99 +113,361 @@ async fn main() {
         }
     };
 
-    // Handle use command
+    // Combine all remaining arguments into a single string
+    let mut question = args.args.join(" ");
+
+    // Handle subcommands
     if let Some(command) = &args.command {
         match command {
-            Commands::Use { target } => {
-                let result = match target {
-                    UseTarget::Provider { name } => config_service.update_provider(name),
-                    UseTarget::Model { name } => config_service.update_model(name),
-                    UseTarget::Key { key } => config_service.update_api_key(key),
+            Commands::Chat => return chat(&args, &config_service, config).await,
+            Commands::Fix { cmd, stderr } => {
+                match FailedCommand::detect(cmd.as_deref(), stderr.as_deref()) {
+                    Ok(failed) => question = failed.to_prompt(),
+                    Err(err) => {
+                        eprintln!("Error: {}", err);
+                        std::process::exit(1);
+                    }
+                }
+            }
+            Commands::Commit => match staged_diff() {
+                Ok(diff) => question = build_commit_prompt(&diff),
+                Err(err) => {
+                    eprintln!("Error: {}", err);
+                    std::process::exit(1);
+                }
+            },
+            Commands::History {
+                action,
+                model,
+                since,
+                until,
+                errors,
+                search,
+                limit,
+            } => {
+                let filter = HistoryFilter {
+                    model: model.clone(),
+                    since: *since,
+                    until: *until,
+                    errors_only: *errors,
+                    search: search.clone(),
                 };
-
+                let result =
+                    show_history(config.log_file.as_ref(), action.as_ref(), &filter, *limit);
                 if let Err(err) = result {
                     eprintln!("Error: {}", err);
                     std::process::exit(1);
                 }
                 return;
             }
+            Commands::Stats { by } => {
+                if let Err(err) = show_stats(config.log_file.as_ref(), *by, &config.prices) {
+                    eprintln!("Error: {}", err);
+                    std::process::exit(1);
+                }
+                return;
+            }
+            Commands::Models {
+                search,
+                free,
+                min_context,
+                refresh,
+            } => {
+                let filter = ModelFilter {
+                    search: search.clone(),
+                    free_only: *free,
+                    min_context: *min_context,
+                };
+                match list_models(&config, &cache_dir(&config_service), *refresh).await {
+                    Ok(models) => show_models(&models, &filter, &config.model),
+                    Err(err) => {
+                        eprintln!("Error: {:#}", err);
+                        std::process::exit(1);
+                    }
+                }
+                return;
+            }
+            Commands::Cache {
+                action: CacheAction::Clear,
+            } => {
+                let cache = ResponseCache::new(cache_dir(&config_service), &config.cache);
+                match cache.clear() {
+                    Ok(count) => println!("✓ Removed {} cached answers", count),
+                    Err(err) => {
+                        eprintln!("Error: {}", err);
+                        std::process::exit(1);
+                    }
+                }
+                return;
+            }
+            Commands::ShellInit { .. } | Commands::Use { .. } => {
+                unreachable!("handled before loading the config")
+            }
         }
     }
 
+    // Without a question there's nothing to answer, so start a conversation
+    if question.is_empty() && args.files.is_empty() && io::stdin().is_terminal() {
+        return chat(&args, &config_service, config).await;
+    }
+
+    // Comparisons log one request per model themselves
+    if !config.models.is_empty() {
+        let succeeded = match build_prompt(&args, &question, &config) {
+            Ok((user_prompt, attached_files)) => {
+                compare_models(&config, &user_prompt, &attached_files).await
+            }
+            Err(err) => {
+                eprintln!("{err:?}");
+                false
+            }
+        };
+        if !succeeded {
+            std::process::exit(1);
+        }
+        return;
+    }
+
     // Normal query mode
     let mut log_entry = RequestLogEntryBuilder::default();
     let total_start = Instant::now();
     log_entry.time(Local::now().to_rfc3339());
 
-    run(&args, &config, &mut log_entry).await;
-
-    let total_duration = total_start.elapsed();
-    log_entry.total_runtime_ms(total_duration.as_millis() as u64);
+    let sessions = SessionStore::new(config_service.get_home_path().join("sessions"));
+    let cache = (config.cache.enabled && !args.no_cache)
+        .then(|| ResponseCache::new(cache_dir(&config_service), &config.cache));
+    let result = run(
+        &args,
+        &question,
+        &config,
+        &sessions,
+        cache.as_ref(),
+        &mut log_entry,
+    )
+    .await;
+    if let Err(err) = &result {
+        log_entry.error(format!("{:?}", err));
+    }
 
-    match log_entry.build() {
-        Ok(log) => {
-            if let Some(log_file) = config.log_file
-                && let Err(err) = log.write_to_file(&log_file)
-            {
-                eprintln!("{}", err);
+    let entry = write_log_entry(&mut log_entry, total_start, config.log_file.as_ref());
+    match config.output {
+        OutputFormat::Text => {
+            if let Err(err) = &result {
+                eprintln!("{err:?}");
+            }
+        }
+        OutputFormat::Json => {
+            if let Some(mut entry) = entry {
+                // The log hides secrets in the answer, scripts get it as is.
+                // Errors fit on one line, without a backtrace.
+                match &result {
+                    Ok(response) => entry.response = response.clone(),
+                    Err(err) => entry.error = format!("{:#}", err),
+                }
+                match serde_json::to_string(&entry) {
+                    Ok(json) => println!("{}", json),
+                    Err(err) => eprintln!("{}", err),
+                }
             }
         }
+    }
+    if result.is_err() {
+        std::process::exit(1);
+    }
+}
+
+fn cache_dir(config_service: &ProdConfigService) -> PathBuf {
+    config_service.get_home_path().join("cache")
+}
+
+/// Checks `name` against the active provider's models. Setup has to work
+/// without a complete config or a reachable provider, so those skip the check.
+async fn validate_model(
+    args: &Args,
+    config_service: &ProdConfigService,
+    name: &str,
+) -> anyhow::Result<()> {
+    let Ok(config) = config_service.load(args) else {
+        return Ok(());
+    };
+    match list_models(&config, &cache_dir(config_service), false).await {
+        Ok(models) => check_model(name, &models),
         Err(err) => {
-            eprintln!("{}", err);
+            eprintln!("\x1b[90mCouldn't check the model name: {:#}\x1b[0m", err);
+            Ok(())
         }
     }
 }
 
-async fn run(args: &Args, config: &Config, log_entry: &mut RequestLogEntryBuilder) {
+async fn chat(args: &Args, config_service: &ProdConfigService, config: Config) {
+    if config.output == OutputFormat::Json {
+        eprintln!("Error: --output json needs a question");
+        std::process::exit(1);
+    }
+
+    let result = match Repl::new(args, config_service, config) {
+        Ok(mut repl) => repl.run().await,
+        Err(err) => Err(err),
+    };
+
+    if let Err(err) = result {
+        eprintln!("Error: {:?}", err);
+        std::process::exit(1);
+    }
+}
+
+/// Attaches files and anything piped into qq as context for the question,
+/// hiding secrets. Returns the prompt and the attached file paths.
+fn build_prompt(
+    args: &Args,
+    question: &str,
+    config: &Config,
+) -> anyhow::Result<(String, Vec<PathBuf>)> {
+    let files = read_files(&args.files)?;
+    let context = read_stdin()?;
+    let user_prompt = build_user_prompt(question, context.as_deref(), &files);
+    let user_prompt = redact_prompt(config, &user_prompt);
+    Ok((
+        user_prompt,
+        files.into_iter().map(|file| file.path).collect(),
+    ))
+}
+
+async fn run(
+    args: &Args,
+    question: &str,
+    config: &Config,
+    sessions: &SessionStore,
+    cache: Option<&ResponseCache>,
+    log_entry: &mut RequestLogEntryBuilder,
+) -> anyhow::Result<String> {
     log_entry.config(config);
 
-    // Combine all remaining arguments into a single string
-    let user_prompt = args.args.join(" ");
+    let (user_prompt, attached_files) = build_prompt(args, question, config)?;
     log_entry.user_prompt(&user_prompt);
+    log_entry.attached_files(attached_files);
 
-    // Dynamically instantiate provider based on config
-    let provider: Box<dyn LLMProvider> = match config.provider {
-        Provider::OpenRouter => Box::new(OpenRouter::new(&config.api_key, &config.model)),
-        Provider::OpenAI => Box::new(OpenAI::new(&config.api_key, &config.model)),
+    // Replay the previous turns of this session when continuing
+    let session = session_key(args.session.as_deref());
+    let mut messages = if args.continue_session {
+        sessions.load(&session)?
+    } else {
+        Vec::new()
     };
+    messages.push(Message::user(&user_prompt));
 
-    let mut spinner = Spinner::new(
-        spinners::Dots,
-        format!("Asking {}", config.model),
-        Color::Blue,
-    );
-    let persona = config.persona.unwrap_or(Persona::Default);
-    let system_prompt = get_system_prompt(persona);
-
-    let llm_start = Instant::now();
-    match provider.prompt(&system_prompt, &user_prompt).await {
-        Ok(response) => {
-            let llm_duration = llm_start.elapsed();
-            log_entry.response(&response);
-            log_entry.llm_response_time_ms(llm_duration.as_millis() as u64);
-            spinner.clear();
-
-            print!("{response}");
-
-            if config.auto_copy && copy_to_clipboard(&response) {
-                print!(" \x1b[90m(copied)\x1b[0m");
-            }
-            println!();
-        }
-        Err(err) => {
-            let llm_duration = llm_start.elapsed();
-            log_entry.error(format!("{:?}", err));
-            log_entry.llm_response_time_ms(llm_duration.as_millis() as u64);
-            spinner.clear();
+    // The user decides what to do with the answer after seeing it. Scripts
+    // only get the answer.
+    let json = config.output == OutputFormat::Json;
+    let committing = matches!(args.command, Some(Commands::Commit)) && !json;
+    let config = &Config {
+        auto_copy: config.auto_copy && !args.execute && !committing && !json,
+        ..config.clone()
+    };
 
-            println!("{err:?}");
-        }
+    let response = ask_with_fallback(config, &messages, cache, log_entry).await?;
+    messages.push(Message::assistant(&response));
+    if let Err(err) = sessions.save(&session, &messages) {
+        eprintln!("{}", err);
+    }
+
+    if committing {
+        confirm_and_commit(&response, log_entry)?;
+    } else if args.execute {
+        confirm_and_run(&response, log_entry)?;
     }
+    Ok(response)
 }
 
-fn copy_to_clipboard(text: &str) -> bool {
-    match Clipboard::new() {
-        Ok(mut cb) => cb.set_text(text).is_ok(),
-        Err(_) => false,
+/// Asks the selected model, then each `[[fallback]]` model in turn for as long
+/// as they fail with errors another model might not run into
+async fn ask_with_fallback(
+    config: &Config,
+    messages: &[Message],
+    cache: Option<&ResponseCache>,
+    log_entry: &mut RequestLogEntryBuilder,
+) -> anyhow::Result<String> {
+    let mut fallback = config.fallback.iter();
+    let mut failed_attempts = Vec::new();
+    // Retries spent on models that failed count too
+    let mut retries = 0;
+    let mut candidate = config.clone();
+    loop {
+        log_entry.config(&candidate);
+        let result = match create_provider(&candidate) {
+            Ok(provider) => {
+                ask(
+                    provider.as_ref(),
+                    &candidate,
+                    messages,
+                    cache,
+                    &mut retries,
+                    log_entry,
+                )
+                .await
+            }
+            Err(err) => Err(err),
+        };
+
+        let err = match result {
+            Ok(response) => {
+                if !failed_attempts.is_empty() && config.output == OutputFormat::Text {
+                    eprintln!(
+                        "\x1b[90m(answered by {} via {})\x1b[0m",
+                        candidate.model, candidate.provider
+                    );
+                }
+                log_entry.failed_attempts(failed_attempts);
+                return Ok(response);
+            }
+            Err(err) => err,
+        };
+
+        // Fallback models whose API key can't be found count as failed too
+        let mut skipped = Vec::new();
+        let next = if is_retryable(&err) {
+            fallback
+                .by_ref()
+                .find_map(|target| match config.with_target(target) {
+                    Ok(next) => Some(next),
+                    Err(key_err) => {
+                        skipped.push(FailedAttempt {
+                            provider: target.provider.to_string(),
+                            model: target.model.clone(),
+                            error: format!("{:#}", key_err),
+                        });
+                        None
+                    }
+                })
+        } else {
+            None
+        };
+        let text = config.output == OutputFormat::Text;
+        if text && next.is_some() {
+            eprintln!("\x1b[90m{} failed: {}\x1b[0m", candidate.model, err);
+        }
+        if text {
+            for attempt in &skipped {
+                eprintln!(
+                    "\x1b[90mSkipped {} via {}: {}\x1b[0m",
+                    attempt.model, attempt.provider, attempt.error
+                );
+            }
+        }
+        let Some(next) = next else {
+            failed_attempts.append(&mut skipped);
+            log_entry.failed_attempts(failed_attempts);
+            return Err(err);
+        };
+        if text {
+            eprintln!("\x1b[90mTrying {} via {}\x1b[0m", next.model, next.provider);
+        }
+        failed_attempts.push(FailedAttempt {
+            provider: candidate.provider.to_string(),
+            model: candidate.model.clone(),
+            error: format!("{:#}", err),
+        });
+        failed_attempts.append(&mut skipped);
+        candidate = next;
     }
 }
@@ -0,0 +1,289 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use anyhow::{Context, Result, anyhow};
+
+use crate::{cache::is_expired, configs::Config, provider::ModelInfo, providers::create_provider};
+
+/// Model lists are fetched again once they're a day old
+const MODEL_LIST_TTL: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// Most close matches suggested for an unknown model
+const MAX_SUGGESTIONS: usize = 5;
+
+/// How similar a model name must be to be suggested, from 0 to 1
+const MIN_SIMILARITY: f64 = 0.85;
+
+/// Criteria for the models listed by `qq models`
+#[derive(Debug, Default)]
+pub struct ModelFilter {
+    pub search: Option<String>,
+    pub free_only: bool,
+    pub min_context: Option<u32>,
+}
+
+impl ModelFilter {
+    pub fn matches(&self, model: &ModelInfo) -> bool {
+        if let Some(search) = &self.search
+            && !model.id.to_lowercase().contains(&search.to_lowercase())
+        {
+            return false;
+        }
+
+        if self.free_only && !model.is_free() {
+            return false;
+        }
+
+        // Models without a known context length can't be shown to satisfy it
+        if let Some(min_context) = self.min_context {
+            return model
+                .context_length
+                .is_some_and(|context_length| context_length >= min_context);
+        }
+
+        true
+    }
+}
+
+/// Models offered by the configured provider. The list is kept in `dir` and
+/// fetched again when it's a day old or `refresh` is set.
+pub async fn list_models(config: &Config, dir: &Path, refresh: bool) -> Result<Vec<ModelInfo>> {
+    let path = model_list_path(config, dir);
+    if !refresh
+        && !is_expired(&path, MODEL_LIST_TTL)
+        && let Some(models) = fs::read_to_string(&path)
+            .ok()
+            .and_then(|json| serde_json::from_str(&json).ok())
+    {
+        return Ok(models);
+    }
+
+    let provider = create_provider(config)?;
+    let mut models = provider.models().await?;
+    models.sort_by(|a, b| a.id.cmp(&b.id));
+
+    fs::create_dir_all(dir).context(format!("Failed to create cache dir {:?}", dir))?;
+    fs::write(&path, serde_json::to_string(&models)?)
+        .context(format!("Failed to write model list {:?}", path))?;
+    Ok(models)
+}
+
+/// Prints the models matching `filter`, marking the active one
+pub fn show_models(models: &[ModelInfo], filter: &ModelFilter, active_model: &str) {
+    let models: Vec<&ModelInfo> = models
+        .iter()
+        .filter(|model| filter.matches(model))
+        .collect();
+    if models.is_empty() {
+        println!("No models match");
+        return;
+    }
+
+    let width = models
+        .iter()
+        .map(|model| model.id.len())
+        .chain(["MODEL".len()])
+        .max()
+        .unwrap_or_default();
+
+    println!(
+        "\x1b[1m  {:<width$}  {:>7}  {:>10}  {:>14}\x1b[0m",
+        "MODEL", "CONTEXT", "PROMPT $/M", "COMPLETION $/M"
+    );
+    for model in models {
+        let marker = if model.id == active_model { "*" } else { " " };
+        let context = model
+            .context_length
+            .map(format_context)
+            .unwrap_or_else(|| String::from("-"));
+        let (prompt, completion) = match model.price {
+            Some(price) => (
+                format!("{:.2}", price.prompt),
+                format!("{:.2}", price.completion),
+            ),
+            None => (String::from("-"), String::from("-")),
+        };
+        println!(
+            "{} {:<width$}  {:>7}  {:>10}  {:>14}",
+            marker, model.id, context, prompt, completion
+        );
+    }
+}
+
+/// Fails unless the provider lists `name`, suggesting the closest names
+pub fn check_model(name: &str, models: &[ModelInfo]) -> Result<()> {
+    if models.is_empty() || models.iter().any(|model| model.id == name) {
+        return Ok(());
+    }
+
+    let mut message = format!("Unknown model '{}'", name);
+    let suggestions = suggestions(name, models);
+    if !suggestions.is_empty() {
+        message.push_str("\n\nDid you mean:");
+        for suggestion in suggestions {
+            message.push_str(&format!("\n  {}", suggestion));
+        }
+    }
+    message.push_str("\n\nRun `qq models` to list the available models, or pass --force");
+    Err(anyhow!(message))
+}
+
+/// Model names closest to `name`, best first. Names are also compared
+/// without their vendor prefix, so "claude-sonnet-4" finds
+/// "anthropic/claude-sonnet-4".
+fn suggestions<'a>(name: &str, models: &'a [ModelInfo]) -> Vec<&'a str> {
+    let name = name.to_lowercase();
+    let mut scored: Vec<(f64, &str)> = models
+        .iter()
+        .map(|model| {
+            let id = model.id.to_lowercase();
+            let short_id = id.rsplit('/').next().unwrap_or(&id);
+            let score = strsim::jaro_winkler(&name, &id).max(strsim::jaro_winkler(&name, short_id));
+            (score, model.id.as_str())
+        })
+        .filter(|(score, _)| *score >= MIN_SIMILARITY)
+        .collect();
+
+    scored.sort_by(|a, b| b.0.total_cmp(&a.0).then_with(|| a.1.cmp(b.1)));
+    scored
+        .into_iter()
+        .take(MAX_SUGGESTIONS)
+        .map(|(_, id)| id)
+        .collect()
+}
+
+/// E.g. "128K" or "1M"
+fn format_context(tokens: u32) -> String {
+    if tokens >= 1_000_000 && tokens.is_multiple_of(1_000_000) {
+        format!("{}M", tokens / 1_000_000)
+    } else if tokens >= 1000 {
+        format!("{}K", tokens / 1000)
+    } else {
+        tokens.to_string()
+    }
+}
+
+/// One list per provider, since each lists different models
+fn model_list_path(config: &Config, dir: &Path) -> PathBuf {
+    dir.join(format!("models-{}.json", config.provider))
+}
+
+#[cfg(test)]
+#[cfg_attr(coverage_nightly, coverage(off))]
+mod tests {
+    use super::*;
+    use crate::configs::ModelPrice;
+
+    fn model(id: &str, context_length: Option<u32>, price: Option<f64>) -> ModelInfo {
+        ModelInfo {
+            id: id.to_string(),
+            context_length,
+            price: price.map(|price| ModelPrice {
+                prompt: price,
+                completion: price,
+            }),
+        }
+    }
+
+    fn models() -> Vec<ModelInfo> {
+        vec![
+            model("anthropic/claude-sonnet-4", Some(200_000), Some(3.0)),
+            model("anthropic/claude-opus-4", Some(200_000), Some(15.0)),
+            model("openai/gpt-4o", Some(128_000), Some(2.5)),
+            model("openai/gpt-4o-mini", Some(128_000), Some(0.15)),
+            model("meta-llama/llama-3.3-70b-instruct:free", Some(65_536), None),
+            model("gpt-4.1", None, None),
+        ]
+    }
+
+    fn ids(models: &[ModelInfo], filter: &ModelFilter) -> Vec<String> {
+        models
+            .iter()
+            .filter(|model| filter.matches(model))
+            .map(|model| model.id.clone())
+            .collect()
+    }
+
+    #[test]
+    fn test_filter_by_name() {
+        let filter = ModelFilter {
+            search: Some("GPT-4o".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(
+            ids(&models(), &filter),
+            vec!["openai/gpt-4o", "openai/gpt-4o-mini"]
+        );
+    }
+
+    #[test]
+    fn test_filter_free_and_context() {
+        let free = ModelFilter {
+            free_only: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            ids(&models(), &free),
+            vec!["meta-llama/llama-3.3-70b-instruct:free"]
+        );
+
+        let long_context = ModelFilter {
+            min_context: Some(150_000),
+            ..Default::default()
+        };
+        assert_eq!(
+            ids(&models(), &long_context),
+            vec!["anthropic/claude-sonnet-4", "anthropic/claude-opus-4"]
+        );
+    }
+
+    #[test]
+    fn test_check_model() {
+        assert!(check_model("openai/gpt-4o", &models()).is_ok());
+        // Providers that list nothing can't be checked
+        assert!(check_model("anything", &[]).is_ok());
+
+        let err = check_model("anthropic/claude-sonet-4", &models()).unwrap_err();
+        assert!(
+            err.to_string()
+                .starts_with("Unknown model 'anthropic/claude-sonet-4'\n\nDid you mean:\n  anthropic/claude-sonnet-4\n")
+        );
+    }
+
+    #[test]
+    fn test_suggestions_ignore_vendor_prefix() {
+        let models = models();
+        assert_eq!(
+            suggestions("claude-sonnet-4", &models)[0],
+            "anthropic/claude-sonnet-4"
+        );
+        assert!(suggestions("kubectl", &models).is_empty());
+    }
+
+    #[test]
+    fn test_format_context() {
+        assert_eq!(format_context(128_000), "128K");
+        assert_eq!(format_context(65_536), "65K");
+        assert_eq!(format_context(1_000_000), "1M");
+        assert_eq!(format_context(512), "512");
+    }
+
+    #[tokio::test]
+    async fn test_list_models_reads_fresh_list() {
+        let dir = std::env::temp_dir().join(format!("qq-models-{}", std::process::id()));
+        let config = Config::default();
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(
+            model_list_path(&config, &dir),
+            serde_json::to_string(&models()).unwrap(),
+        )
+        .unwrap();
+
+        let listed = list_models(&config, &dir, false).await.unwrap();
+        assert_eq!(listed, models());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -1,8 +1,100 @@
-use clap::ValueEnum;
-use serde::{Deserialize, Serialize};
+use crate::{
+    configs::PersonaConfig,
+    prompts::{COMMIT_PROMPT, FIX_PROMPT, SYSTEM_PROMPT},
+};
 
-#[derive(Debug, Deserialize, Serialize, Clone, ValueEnum, Copy, PartialEq)]
-#[serde(rename_all = "lowercase")]
-pub enum Persona {
-    Default,
+pub const DEFAULT_PERSONA: &str = "default";
+/// Used by `qq fix` unless another persona is picked
+pub const FIX_PERSONA: &str = "fix";
+/// Used by `qq commit` unless another persona is picked
+pub const COMMIT_PERSONA: &str = "commit";
+
+/// A named system prompt, optionally tied to model settings
+#[derive(Debug, Clone, PartialEq)]
+pub struct Persona {
+    pub name: String,
+    pub system_prompt: String,
+    pub model: Option<String>,
+    pub temperature: Option<f32>,
+}
+
+impl Persona {
+    /// Looks up one of the personas shipped with qq
+    pub fn builtin(name: &str) -> Option<Self> {
+        let system_prompt = match name {
+            DEFAULT_PERSONA => SYSTEM_PROMPT,
+            FIX_PERSONA => FIX_PROMPT,
+            COMMIT_PERSONA => COMMIT_PROMPT,
+            _ => return None,
+        };
+
+        Some(Self {
+            name: name.to_string(),
+            system_prompt: system_prompt.to_string(),
+            model: None,
+            temperature: None,
+        })
+    }
+
+    pub fn from_config(name: &str, config: &PersonaConfig) -> Self {
+        Self {
+            name: name.to_string(),
+            system_prompt: config.system_prompt.clone(),
+            model: config.model.clone(),
+            temperature: config.temperature,
+        }
+    }
+}
+
+impl Default for Persona {
+    fn default() -> Self {
+        Self::builtin(DEFAULT_PERSONA).expect("default persona is built in")
+    }
+}
+
+#[cfg(test)]
+#[cfg_attr(coverage_nightly, coverage(off))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_builtin_default() {
+        let persona = Persona::builtin("default").unwrap();
+        assert_eq!(persona.name, "default");
+        assert_eq!(persona.system_prompt, SYSTEM_PROMPT);
+        assert_eq!(persona, Persona::default());
+    }
+
+    #[test]
+    fn test_builtin_fix() {
+        let persona = Persona::builtin("fix").unwrap();
+        assert_eq!(persona.name, "fix");
+        assert_eq!(persona.system_prompt, FIX_PROMPT);
+    }
+
+    #[test]
+    fn test_builtin_commit() {
+        let persona = Persona::builtin("commit").unwrap();
+        assert_eq!(persona.system_prompt, COMMIT_PROMPT);
+    }
+
+    #[test]
+    fn test_builtin_unknown() {
+        assert!(Persona::builtin("pirate").is_none());
+    }
+
+    #[test]
+    fn test_from_config() {
+        let config = PersonaConfig {
+            system_prompt: "Explain commands step by step".to_string(),
+            model: Some("openai/gpt-4o".to_string()),
+            temperature: Some(0.7),
+        };
+
+        let persona = Persona::from_config("explain", &config);
+        assert_eq!(persona.name, "explain");
+        assert_eq!(persona.system_prompt, "Explain commands step by step");
+        assert_eq!(persona.model.as_deref(), Some("openai/gpt-4o"));
+        assert_eq!(persona.temperature, Some(0.7));
+    }
 }
@@ -0,0 +1,115 @@
+use std::collections::HashMap;
+
+use crate::{configs::ModelPrice, provider::TokenUsage};
+
+/// Prices in USD per million prompt and completion tokens. Models are matched
+/// with and without the provider prefix, e.g. `openai/gpt-4o` uses `gpt-4o`.
+const BUILTIN_PRICES: [(&str, f64, f64); 14] = [
+    ("gpt-5", 1.25, 10.0),
+    ("gpt-5-mini", 0.25, 2.0),
+    ("gpt-5-nano", 0.05, 0.4),
+    ("gpt-4.1", 2.0, 8.0),
+    ("gpt-4.1-mini", 0.4, 1.6),
+    ("gpt-4.1-nano", 0.1, 0.4),
+    ("gpt-4o", 2.5, 10.0),
+    ("gpt-4o-mini", 0.15, 0.6),
+    ("claude-opus-4-1", 15.0, 75.0),
+    ("claude-sonnet-4-5", 3.0, 15.0),
+    ("claude-haiku-4-5", 1.0, 5.0),
+    ("claude-3.5-sonnet", 3.0, 15.0),
+    ("claude-3-5-haiku", 0.8, 4.0),
+    ("gemini-2.5-flash", 0.3, 2.5),
+];
+
+/// The built-in prices, overridden and extended by the user's `[prices]`
+pub fn price_table(overrides: Option<&HashMap<String, ModelPrice>>) -> HashMap<String, ModelPrice> {
+    let mut prices: HashMap<String, ModelPrice> = BUILTIN_PRICES
+        .iter()
+        .map(|&(model, prompt, completion)| (model.to_string(), ModelPrice { prompt, completion }))
+        .collect();
+    if let Some(overrides) = overrides {
+        prices.extend(overrides.clone());
+    }
+    prices
+}
+
+/// Cost of a request in USD: what the provider reported, or else an estimate
+/// from the price table. Unknown models have no cost.
+pub fn request_cost(
+    usage: &TokenUsage,
+    model: &str,
+    prices: &HashMap<String, ModelPrice>,
+) -> Option<f64> {
+    if usage.cost.is_some() {
+        return usage.cost;
+    }
+
+    let price = prices.get(model).or_else(|| {
+        let (_, name) = model.rsplit_once('/')?;
+        prices.get(name)
+    })?;
+    let cost = usage.prompt_tokens as f64 * price.prompt
+        + usage.completion_tokens as f64 * price.completion;
+    Some(cost / 1_000_000.0)
+}
+
+#[cfg(test)]
+#[cfg_attr(coverage_nightly, coverage(off))]
+mod tests {
+    use super::*;
+
+    fn usage(prompt_tokens: u32, completion_tokens: u32) -> TokenUsage {
+        TokenUsage {
+            prompt_tokens,
+            completion_tokens,
+            cost: None,
+        }
+    }
+
+    #[test]
+    fn test_cost_from_price_table() {
+        let prices = price_table(None);
+        let cost = request_cost(&usage(1_000_000, 100_000), "gpt-4o", &prices).unwrap();
+        assert!((cost - 3.5).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_cost_ignores_provider_prefix() {
+        let prices = price_table(None);
+        assert_eq!(
+            request_cost(&usage(1000, 0), "anthropic/claude-sonnet-4-5", &prices),
+            request_cost(&usage(1000, 0), "claude-sonnet-4-5", &prices)
+        );
+    }
+
+    #[test]
+    fn test_reported_cost_wins() {
+        let prices = price_table(None);
+        let reported = TokenUsage {
+            cost: Some(0.42),
+            ..usage(1000, 1000)
+        };
+        assert_eq!(request_cost(&reported, "gpt-4o", &prices), Some(0.42));
+    }
+
+    #[test]
+    fn test_user_prices() {
+        let overrides = HashMap::from([(
+            "qwen2.5-coder".to_string(),
+            ModelPrice {
+                prompt: 0.0,
+                completion: 0.0,
+            },
+        )]);
+        let prices = price_table(Some(&overrides));
+
+        assert_eq!(
+            request_cost(&usage(500, 500), "qwen2.5-coder", &prices),
+            Some(0.0)
+        );
+        assert_eq!(
+            request_cost(&usage(500, 500), "unknown-model", &prices),
+            None
+        );
+    }
+}
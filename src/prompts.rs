@@ -1,14 +1,21 @@
-use crate::persona::Persona;
+use crate::{persona::Persona, system_info::SystemInfo};
 
-const SYSTEM_PROMPT: &str = r#"You are a helpful assistant that provides concise, minimal responses.
+pub const SYSTEM_PROMPT: &str = r#"You are a helpful assistant that provides concise, minimal responses.
 When asked how to do something, provide ONLY the command or code needed, without any explanation.
 Your output should be directly usable - no formatting, no explanations, no extra text.
 For example, if asked "how to make a git commit", respond with only: git commit -m ""
-Keep responses minimal and executable. 
-You are running on macos, make sure to return a compatible command"#;
+Keep responses minimal and executable."#;
 
-pub fn get_system_prompt(persona: Persona) -> String {
-    match persona {
-        Persona::Default => String::from(SYSTEM_PROMPT),
-    }
+pub const FIX_PROMPT: &str = r#"You fix shell commands that failed.
+You are given a command, and possibly its exit status and error output.
+Respond with ONLY the corrected command, without any explanation or formatting.
+If the command cannot be fixed, respond with one short line explaining why."#;
+
+pub const COMMIT_PROMPT: &str = r#"You write git commit messages for the staged changes you are given.
+Start with a summary line in the imperative mood of at most 72 characters.
+If the change needs more context, add a blank line and a short body explaining what changed and why.
+Respond with ONLY the commit message, without any formatting or extra text."#;
+
+pub fn get_system_prompt(persona: &Persona, system_info: &SystemInfo) -> String {
+    format!("{}\n{}", persona.system_prompt, system_info.describe())
 }
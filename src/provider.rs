@@ -1,19 +1,341 @@
+use std::{convert::Infallible, fmt, ops::Add, pin::Pin, str::FromStr, time::Duration};
+
 use anyhow::Result;
 use async_trait::async_trait;
-use clap::ValueEnum;
+use futures::Stream;
+use reqwest::{
+    StatusCode,
+    header::{HeaderMap, RETRY_AFTER},
+};
 use serde::{Deserialize, Serialize};
 
-#[derive(Deserialize, Serialize, Clone, ValueEnum, Debug, PartialEq, Hash, Eq)]
-#[serde(rename_all = "lowercase")]
-#[value(rename_all = "lowercase")]
-#[derive(Default)]
+use crate::configs::ModelPrice;
+
+#[derive(Deserialize, Serialize, Clone, Debug, PartialEq, Hash, Eq, Default)]
+#[serde(from = "String", into = "String")]
 pub enum Provider {
     #[default]
-    OpenRouter = 0,
-    OpenAI = 1,
+    OpenRouter,
+    OpenAI,
+    Anthropic,
+    Ollama,
+    /// Any other OpenAI-compatible endpoint, identified by its config name
+    Custom(String),
+}
+
+impl Provider {
+    /// Whether requests to this provider need an API key
+    pub fn requires_api_key(&self) -> bool {
+        !matches!(self, Provider::Ollama | Provider::Custom(_))
+    }
+}
+
+impl FromStr for Provider {
+    type Err = Infallible;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        Ok(match name.to_lowercase().as_str() {
+            "openrouter" => Provider::OpenRouter,
+            "openai" => Provider::OpenAI,
+            "anthropic" => Provider::Anthropic,
+            "ollama" => Provider::Ollama,
+            _ => Provider::Custom(name.to_string()),
+        })
+    }
+}
+
+impl fmt::Display for Provider {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Provider::OpenRouter => write!(f, "openrouter"),
+            Provider::OpenAI => write!(f, "openai"),
+            Provider::Anthropic => write!(f, "anthropic"),
+            Provider::Ollama => write!(f, "ollama"),
+            Provider::Custom(name) => write!(f, "{}", name),
+        }
+    }
+}
+
+impl From<String> for Provider {
+    fn from(name: String) -> Self {
+        let Ok(provider) = name.parse();
+        provider
+    }
+}
+
+impl From<Provider> for String {
+    fn from(provider: Provider) -> Self {
+        provider.to_string()
+    }
+}
+
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Role {
+    User,
+    Assistant,
+}
+
+/// A single turn of a conversation
+#[derive(Deserialize, Serialize, Clone, Debug, PartialEq)]
+pub struct Message {
+    pub role: Role,
+    pub content: String,
+}
+
+impl Message {
+    pub fn user(content: impl Into<String>) -> Self {
+        Self {
+            role: Role::User,
+            content: content.into(),
+        }
+    }
+
+    pub fn assistant(content: impl Into<String>) -> Self {
+        Self {
+            role: Role::Assistant,
+            content: content.into(),
+        }
+    }
+}
+
+/// Tokens spent on a request, as reported by the provider
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, Default, PartialEq)]
+pub struct TokenUsage {
+    pub prompt_tokens: u32,
+    pub completion_tokens: u32,
+    /// Cost in USD, for providers that report it (OpenRouter)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cost: Option<f64>,
 }
 
+impl Add for TokenUsage {
+    type Output = Self;
+
+    fn add(self, other: Self) -> Self {
+        let cost = match (self.cost, other.cost) {
+            (Some(a), Some(b)) => Some(a + b),
+            (cost, None) | (None, cost) => cost,
+        };
+        Self {
+            prompt_tokens: self.prompt_tokens + other.prompt_tokens,
+            completion_tokens: self.completion_tokens + other.completion_tokens,
+            cost,
+        }
+    }
+}
+
+/// A complete answer from the model
+#[derive(Clone, Debug, PartialEq)]
+pub struct Completion {
+    pub content: String,
+    pub usage: Option<TokenUsage>,
+}
+
+/// A piece of a streamed answer. Usage may be reported in several parts,
+/// which add up to the usage of the whole request.
+#[derive(Clone, Debug, PartialEq)]
+pub enum StreamEvent {
+    Delta(String),
+    Usage(TokenUsage),
+}
+
+/// Stream of response events, in the order they were generated
+pub type ResponseStream = Pin<Box<dyn Stream<Item = Result<StreamEvent>> + Send>>;
+
+/// An error status returned by a provider's API
+#[derive(Debug)]
+pub struct ApiError {
+    pub status: StatusCode,
+    /// How long the provider asked us to wait before trying again
+    pub retry_after: Option<Duration>,
+    pub message: String,
+}
+
+impl ApiError {
+    pub fn new(status: StatusCode, headers: &HeaderMap, message: impl Into<String>) -> Self {
+        let retry_after = headers
+            .get(RETRY_AFTER)
+            .and_then(|value| value.to_str().ok())
+            .and_then(parse_retry_after);
+        Self {
+            status,
+            retry_after,
+            message: message.into(),
+        }
+    }
+
+    /// Rate limits, timeouts and server errors may go away on their own
+    pub fn is_retryable(&self) -> bool {
+        matches!(
+            self.status,
+            StatusCode::TOO_MANY_REQUESTS | StatusCode::REQUEST_TIMEOUT
+        ) || self.status.is_server_error()
+    }
+}
+
+impl fmt::Display for ApiError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+impl std::error::Error for ApiError {}
+
+/// `Retry-After` holds either a number of seconds or an HTTP date
+fn parse_retry_after(value: &str) -> Option<Duration> {
+    if let Ok(seconds) = value.trim().parse::<u64>() {
+        return Some(Duration::from_secs(seconds));
+    }
+    let date = chrono::DateTime::parse_from_rfc2822(value.trim()).ok()?;
+    let seconds = (date.timestamp() - chrono::Utc::now().timestamp()).max(0);
+    Some(Duration::from_secs(seconds as u64))
+}
+
+/// A model listed by a provider. Only OpenRouter reports context lengths and
+/// prices.
+#[derive(Deserialize, Serialize, Clone, Debug, PartialEq)]
+pub struct ModelInfo {
+    pub id: String,
+    pub context_length: Option<u32>,
+    pub price: Option<ModelPrice>,
+}
+
+impl ModelInfo {
+    pub fn is_free(&self) -> bool {
+        self.id.ends_with(":free")
+            || self
+                .price
+                .is_some_and(|price| price.prompt == 0.0 && price.completion == 0.0)
+    }
+}
+
+/// `messages` holds the conversation so far, ending with the user's question
 #[async_trait]
 pub trait LLMProvider {
-    async fn prompt(&self, system_prompt: &str, user_prompt: &str) -> Result<String>;
+    async fn prompt(&self, system_prompt: &str, messages: &[Message]) -> Result<Completion>;
+
+    async fn prompt_stream(
+        &self,
+        system_prompt: &str,
+        messages: &[Message],
+    ) -> Result<ResponseStream>;
+
+    /// Models the provider offers
+    async fn models(&self) -> Result<Vec<ModelInfo>>;
+}
+
+#[cfg(test)]
+#[cfg_attr(coverage_nightly, coverage(off))]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+
+    #[test]
+    fn test_parse_builtin_providers() {
+        assert_eq!("openrouter".parse(), Ok(Provider::OpenRouter));
+        assert_eq!("OpenAI".parse(), Ok(Provider::OpenAI));
+        assert_eq!("anthropic".parse(), Ok(Provider::Anthropic));
+        assert_eq!("ollama".parse(), Ok(Provider::Ollama));
+    }
+
+    #[test]
+    fn test_parse_custom_provider() {
+        assert_eq!("vllm".parse(), Ok(Provider::Custom("vllm".to_string())));
+    }
+
+    #[test]
+    fn test_provider_names_round_trip() {
+        for provider in [
+            Provider::OpenRouter,
+            Provider::OpenAI,
+            Provider::Anthropic,
+            Provider::Ollama,
+            Provider::Custom("lmstudio".to_string()),
+        ] {
+            assert_eq!(provider.to_string().parse(), Ok(provider));
+        }
+    }
+
+    #[test]
+    fn test_provider_as_toml_table_key() {
+        let providers: HashMap<Provider, String> = toml::from_str(
+            r#"
+            openrouter = "a"
+            gateway = "b"
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(providers[&Provider::OpenRouter], "a");
+        assert_eq!(providers[&Provider::Custom("gateway".to_string())], "b");
+    }
+
+    #[test]
+    fn test_api_error_retry_after() {
+        let mut headers = HeaderMap::new();
+        headers.insert(RETRY_AFTER, "7".parse().unwrap());
+        let error = ApiError::new(StatusCode::TOO_MANY_REQUESTS, &headers, "slow down");
+
+        assert!(error.is_retryable());
+        assert_eq!(error.retry_after, Some(Duration::from_secs(7)));
+        assert_eq!(error.to_string(), "slow down");
+        assert_eq!(
+            parse_retry_after("Wed, 21 Oct 2015 07:28:00 GMT"),
+            Some(Duration::ZERO)
+        );
+    }
+
+    #[test]
+    fn test_api_error_is_retryable() {
+        let headers = HeaderMap::new();
+        assert!(ApiError::new(StatusCode::BAD_GATEWAY, &headers, "").is_retryable());
+        assert!(!ApiError::new(StatusCode::UNAUTHORIZED, &headers, "").is_retryable());
+        assert!(!ApiError::new(StatusCode::BAD_REQUEST, &headers, "").is_retryable());
+    }
+
+    #[test]
+    fn test_add_token_usage() {
+        let prompt = TokenUsage {
+            prompt_tokens: 25,
+            ..Default::default()
+        };
+        let completion = TokenUsage {
+            completion_tokens: 10,
+            cost: Some(0.001),
+            ..Default::default()
+        };
+
+        assert_eq!(
+            prompt + completion,
+            TokenUsage {
+                prompt_tokens: 25,
+                completion_tokens: 10,
+                cost: Some(0.001),
+            }
+        );
+    }
+
+    #[test]
+    fn test_model_is_free() {
+        let model = |id: &str, price: Option<ModelPrice>| ModelInfo {
+            id: id.to_string(),
+            context_length: None,
+            price,
+        };
+        let free = ModelPrice {
+            prompt: 0.0,
+            completion: 0.0,
+        };
+        let paid = ModelPrice {
+            prompt: 3.0,
+            completion: 15.0,
+        };
+
+        assert!(model("meta-llama/llama-3.3-70b-instruct:free", None).is_free());
+        assert!(model("openrouter/sonoma", Some(free)).is_free());
+        assert!(!model("anthropic/claude-sonnet-4", Some(paid)).is_free());
+        assert!(!model("gpt-4o", None).is_free());
+    }
 }
@@ -0,0 +1,448 @@
+use std::time::Duration;
+
+use anyhow::{Context, Result, anyhow};
+use async_trait::async_trait;
+use eventsource_stream::Eventsource;
+use futures::StreamExt;
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    provider::{
+        ApiError, Completion, LLMProvider, Message, ModelInfo, ResponseStream, StreamEvent,
+        TokenUsage,
+    },
+    providers::helpers::{http_client, parse_models},
+};
+
+const ANTHROPIC_API_BASE: &str = "https://api.anthropic.com/v1";
+const ANTHROPIC_VERSION: &str = "2023-06-01";
+// The Messages API requires an explicit output limit
+const DEFAULT_MAX_TOKENS: u32 = 4096;
+
+pub struct Anthropic {
+    client: reqwest::Client,
+    base_url: String,
+    model: String,
+    temperature: Option<f32>,
+}
+
+impl Anthropic {
+    pub fn new(api_key: &str, model: &str, headers: HeaderMap, timeout: Duration) -> Self {
+        Self::with_base_url(api_key, model, ANTHROPIC_API_BASE, headers, timeout)
+    }
+
+    /// Configured `headers` replace the default ones of the same name
+    pub fn with_base_url(
+        api_key: &str,
+        model: &str,
+        base_url: &str,
+        headers: HeaderMap,
+        timeout: Duration,
+    ) -> Self {
+        let mut all_headers = Self::get_headers(api_key);
+        all_headers.extend(headers);
+        let client = http_client(all_headers, timeout);
+        Self {
+            client,
+            base_url: base_url.trim_end_matches('/').to_string(),
+            model: model.to_string(),
+            temperature: None,
+        }
+    }
+
+    pub fn with_temperature(mut self, temperature: Option<f32>) -> Self {
+        self.temperature = temperature;
+        self
+    }
+
+    fn get_headers(api_key: &str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        if let Ok(api_key) = HeaderValue::from_str(api_key) {
+            headers.insert(HeaderName::from_static("x-api-key"), api_key);
+        }
+        headers.insert(
+            HeaderName::from_static("anthropic-version"),
+            HeaderValue::from_static(ANTHROPIC_VERSION),
+        );
+        headers
+    }
+
+    async fn send(&self, request: &MessagesRequest<'_>) -> Result<reqwest::Response> {
+        let response = self
+            .client
+            .post(format!("{}/messages", self.base_url))
+            .json(request)
+            .send()
+            .await
+            .context("Failed to get response")?;
+
+        let status = response.status();
+        if !status.is_success() {
+            let headers = response.headers().clone();
+            let body = response.text().await.unwrap_or_default();
+            return Err(parse_error(status, &headers, &body).into());
+        }
+
+        Ok(response)
+    }
+}
+
+#[async_trait]
+impl LLMProvider for Anthropic {
+    async fn prompt(&self, system_prompt: &str, messages: &[Message]) -> Result<Completion> {
+        let request = MessagesRequest::new(&self.model, system_prompt, messages, false)
+            .with_temperature(self.temperature);
+
+        let body = self
+            .send(&request)
+            .await?
+            .text()
+            .await
+            .context("Failed to read response")?;
+
+        parse_response(&body)
+    }
+
+    async fn prompt_stream(
+        &self,
+        system_prompt: &str,
+        messages: &[Message],
+    ) -> Result<ResponseStream> {
+        let request = MessagesRequest::new(&self.model, system_prompt, messages, true)
+            .with_temperature(self.temperature);
+
+        let stream = self
+            .send(&request)
+            .await?
+            .bytes_stream()
+            .eventsource()
+            .filter_map(|event| async move {
+                match event {
+                    Ok(event) => parse_stream_event(&event.event, &event.data).transpose(),
+                    Err(err) => Some(Err(anyhow!("Failed to read response stream: {}", err))),
+                }
+            })
+            .boxed();
+
+        Ok(stream)
+    }
+
+    async fn models(&self) -> Result<Vec<ModelInfo>> {
+        // The list is paged, 20 models at a time unless asked for more
+        let response = self
+            .client
+            .get(format!("{}/models?limit=1000", self.base_url))
+            .send()
+            .await
+            .context("Failed to list models")?;
+
+        let status = response.status();
+        let headers = response.headers().clone();
+        let body = response.text().await.context("Failed to read model list")?;
+        if !status.is_success() {
+            return Err(parse_error(status, &headers, &body).into());
+        }
+        parse_models(&body)
+    }
+}
+
+#[derive(Serialize, Debug)]
+struct MessagesRequest<'a> {
+    model: &'a str,
+    max_tokens: u32,
+    system: &'a str,
+    // Our messages already use the Messages API shape: `{ role, content }`
+    messages: &'a [Message],
+    #[serde(skip_serializing_if = "Option::is_none")]
+    temperature: Option<f32>,
+    stream: bool,
+}
+
+impl<'a> MessagesRequest<'a> {
+    fn new(model: &'a str, system_prompt: &'a str, messages: &'a [Message], stream: bool) -> Self {
+        Self {
+            model,
+            max_tokens: DEFAULT_MAX_TOKENS,
+            system: system_prompt,
+            messages,
+            temperature: None,
+            stream,
+        }
+    }
+
+    fn with_temperature(mut self, temperature: Option<f32>) -> Self {
+        self.temperature = temperature;
+        self
+    }
+}
+
+#[derive(Deserialize)]
+struct MessagesResponse {
+    content: Vec<ContentBlock>,
+    usage: Option<Usage>,
+}
+
+#[derive(Deserialize)]
+struct Usage {
+    #[serde(default)]
+    input_tokens: u32,
+    #[serde(default)]
+    output_tokens: u32,
+}
+
+impl From<Usage> for TokenUsage {
+    fn from(usage: Usage) -> Self {
+        Self {
+            prompt_tokens: usage.input_tokens,
+            completion_tokens: usage.output_tokens,
+            cost: None,
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct ContentBlock {
+    #[serde(default)]
+    text: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct ContentBlockDelta {
+    delta: ContentBlock,
+}
+
+#[derive(Deserialize)]
+struct MessageStart {
+    message: MessageStartBody,
+}
+
+#[derive(Deserialize)]
+struct MessageStartBody {
+    usage: Usage,
+}
+
+#[derive(Deserialize)]
+struct MessageDelta {
+    usage: Usage,
+}
+
+#[derive(Deserialize)]
+struct ErrorResponse {
+    error: ErrorDetails,
+}
+
+#[derive(Deserialize)]
+struct ErrorDetails {
+    r#type: String,
+    message: String,
+}
+
+fn parse_response(body: &str) -> Result<Completion> {
+    let response: MessagesResponse =
+        serde_json::from_str(body).context("Failed to parse response")?;
+
+    let text: String = response
+        .content
+        .into_iter()
+        .filter_map(|block| block.text)
+        .collect();
+
+    if text.is_empty() {
+        return Err(anyhow!("Response is empty or contains no content"));
+    }
+    Ok(Completion {
+        content: text,
+        usage: response.usage.map(TokenUsage::from),
+    })
+}
+
+/// Returns the text or usage carried by a single server-sent event, if any.
+/// The prompt tokens arrive when the message starts and the completion tokens
+/// when it ends.
+fn parse_stream_event(event: &str, data: &str) -> Result<Option<StreamEvent>> {
+    match event {
+        "content_block_delta" => {
+            let delta: ContentBlockDelta =
+                serde_json::from_str(data).context("Failed to parse stream event")?;
+            Ok(delta
+                .delta
+                .text
+                .filter(|text| !text.is_empty())
+                .map(StreamEvent::Delta))
+        }
+        "message_start" => {
+            let start: MessageStart =
+                serde_json::from_str(data).context("Failed to parse stream event")?;
+            Ok(Some(StreamEvent::Usage(TokenUsage {
+                prompt_tokens: start.message.usage.input_tokens,
+                ..Default::default()
+            })))
+        }
+        "message_delta" => {
+            let delta: MessageDelta =
+                serde_json::from_str(data).context("Failed to parse stream event")?;
+            Ok(Some(StreamEvent::Usage(TokenUsage {
+                completion_tokens: delta.usage.output_tokens,
+                ..Default::default()
+            })))
+        }
+        "error" => {
+            let error: ErrorResponse =
+                serde_json::from_str(data).context("Failed to parse stream error")?;
+            Err(anyhow!("{}: {}", error.error.r#type, error.error.message))
+        }
+        _ => Ok(None),
+    }
+}
+
+fn parse_error(status: reqwest::StatusCode, headers: &HeaderMap, body: &str) -> ApiError {
+    let message = match serde_json::from_str::<ErrorResponse>(body) {
+        Ok(error) => format!(
+            "{} ({}): {}",
+            error.error.r#type, status, error.error.message
+        ),
+        Err(_) => format!("Request failed with status {}: {}", status, body),
+    };
+    ApiError::new(status, headers, message)
+}
+
+#[cfg(test)]
+#[cfg_attr(coverage_nightly, coverage(off))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_get_headers() {
+        let headers = Anthropic::get_headers("test-key");
+        assert_eq!(headers.len(), 2);
+        assert_eq!(headers.get("x-api-key").unwrap(), "test-key");
+        assert_eq!(headers.get("anthropic-version").unwrap(), ANTHROPIC_VERSION);
+    }
+
+    #[test]
+    fn test_messages_request_uses_top_level_system() {
+        let messages = [Message::user("What is 2+2?")];
+        let request = MessagesRequest::new("claude-sonnet-4-5", "Be brief", &messages, false);
+        let json = serde_json::to_value(&request).unwrap();
+
+        assert_eq!(
+            json,
+            serde_json::json!({
+                "model": "claude-sonnet-4-5",
+                "max_tokens": DEFAULT_MAX_TOKENS,
+                "system": "Be brief",
+                "messages": [{ "role": "user", "content": "What is 2+2?" }],
+                "stream": false
+            })
+        );
+    }
+
+    #[test]
+    fn test_messages_request_with_history() {
+        let messages = [
+            Message::user("list files"),
+            Message::assistant("ls"),
+            Message::user("sorted by size"),
+        ];
+        let request = MessagesRequest::new("claude-sonnet-4-5", "Be brief", &messages, false);
+        let json = serde_json::to_value(&request).unwrap();
+
+        assert_eq!(
+            json["messages"],
+            serde_json::json!([
+                { "role": "user", "content": "list files" },
+                { "role": "assistant", "content": "ls" },
+                { "role": "user", "content": "sorted by size" }
+            ])
+        );
+    }
+
+    #[test]
+    fn test_messages_request_with_temperature() {
+        let messages = [Message::user("Hi")];
+        let request = MessagesRequest::new("claude-sonnet-4-5", "Be brief", &messages, true)
+            .with_temperature(Some(0.5));
+        let json = serde_json::to_value(&request).unwrap();
+
+        assert_eq!(json["temperature"], 0.5);
+        assert_eq!(json["stream"], true);
+    }
+
+    #[test]
+    fn test_parse_response() {
+        let body = r#"{
+            "id": "msg_01",
+            "type": "message",
+            "role": "assistant",
+            "content": [{ "type": "text", "text": "4" }],
+            "stop_reason": "end_turn",
+            "usage": { "input_tokens": 12, "output_tokens": 1 }
+        }"#;
+
+        assert_eq!(
+            parse_response(body).unwrap(),
+            Completion {
+                content: "4".to_string(),
+                usage: Some(TokenUsage {
+                    prompt_tokens: 12,
+                    completion_tokens: 1,
+                    cost: None,
+                }),
+            }
+        );
+    }
+
+    #[test]
+    fn test_parse_response_without_text() {
+        let body = r#"{ "content": [] }"#;
+        assert!(parse_response(body).is_err());
+    }
+
+    #[test]
+    fn test_parse_stream_event() {
+        let delta = r#"{"type":"content_block_delta","index":0,"delta":{"type":"text_delta","text":"ls -la"}}"#;
+        assert_eq!(
+            parse_stream_event("content_block_delta", delta).unwrap(),
+            Some(StreamEvent::Delta("ls -la".to_string()))
+        );
+
+        assert_eq!(
+            parse_stream_event("ping", r#"{"type":"ping"}"#).unwrap(),
+            None
+        );
+
+        let start = r#"{"type":"message_start","message":{"id":"msg_01","usage":{"input_tokens":25,"output_tokens":1}}}"#;
+        assert_eq!(
+            parse_stream_event("message_start", start).unwrap(),
+            Some(StreamEvent::Usage(TokenUsage {
+                prompt_tokens: 25,
+                ..Default::default()
+            }))
+        );
+
+        let end = r#"{"type":"message_delta","delta":{"stop_reason":"end_turn"},"usage":{"output_tokens":15}}"#;
+        assert_eq!(
+            parse_stream_event("message_delta", end).unwrap(),
+            Some(StreamEvent::Usage(TokenUsage {
+                completion_tokens: 15,
+                ..Default::default()
+            }))
+        );
+
+        let error =
+            r#"{"type":"error","error":{"type":"overloaded_error","message":"Overloaded"}}"#;
+        let err = parse_stream_event("error", error).unwrap_err();
+        assert_eq!(err.to_string(), "overloaded_error: Overloaded");
+    }
+
+    #[test]
+    fn test_parse_error() {
+        let body = r#"{"type":"error","error":{"type":"authentication_error","message":"invalid x-api-key"}}"#;
+        let err = parse_error(reqwest::StatusCode::UNAUTHORIZED, &HeaderMap::new(), body);
+        assert_eq!(
+            err.to_string(),
+            "authentication_error (401 Unauthorized): invalid x-api-key"
+        );
+    }
+}
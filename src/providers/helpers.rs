@@ -1,43 +1,355 @@
-use anyhow::{Context, Result};
+use std::{collections::HashMap, time::Duration};
+
+use anyhow::{Context, Result, anyhow};
 use async_openai::types::{
-    ChatCompletionRequestMessage, ChatCompletionRequestSystemMessageArgs,
-    ChatCompletionRequestUserMessageArgs, CreateChatCompletionRequest,
-    CreateChatCompletionRequestArgs,
+    ChatCompletionRequestAssistantMessageArgs, ChatCompletionRequestMessage,
+    ChatCompletionRequestSystemMessageArgs, ChatCompletionRequestUserMessageArgs,
+    ChatCompletionStreamOptions, CreateChatCompletionRequest, CreateChatCompletionRequestArgs,
+};
+use eventsource_stream::Eventsource;
+use futures::{Stream, StreamExt};
+use reqwest::header::{AUTHORIZATION, HeaderMap, HeaderName, HeaderValue};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    configs::ModelPrice,
+    provider::{
+        ApiError, Completion, Message, ModelInfo, ResponseStream, Role, StreamEvent, TokenUsage,
+    },
 };
 
+/// Sends chat completion requests to an OpenAI-compatible API
+pub(crate) struct ChatClient {
+    http_client: reqwest::Client,
+    base_url: String,
+}
+
+impl ChatClient {
+    /// The API key is sent as a bearer token, unless it's empty
+    pub(crate) fn new(
+        api_key: &str,
+        base_url: &str,
+        mut headers: HeaderMap,
+        timeout: Duration,
+    ) -> Self {
+        if !api_key.is_empty()
+            && let Ok(authorization) = HeaderValue::from_str(&format!("Bearer {}", api_key))
+        {
+            headers.insert(AUTHORIZATION, authorization);
+        }
+        let http_client = http_client(headers, timeout);
+        Self {
+            http_client,
+            base_url: base_url.trim_end_matches('/').to_string(),
+        }
+    }
+
+    async fn send(&self, request: &(impl Serialize + Sync)) -> Result<reqwest::Response> {
+        let response = self
+            .http_client
+            .post(format!("{}/chat/completions", self.base_url))
+            .json(request)
+            .send()
+            .await
+            .context("Failed to get response")?;
+
+        let status = response.status();
+        if !status.is_success() {
+            let headers = response.headers().clone();
+            let body = response.text().await.unwrap_or_default();
+            return Err(parse_error(status, &headers, &body).into());
+        }
+
+        Ok(response)
+    }
+
+    /// Lists the models from `/models`
+    pub(crate) async fn models(&self) -> Result<Vec<ModelInfo>> {
+        let response = self
+            .http_client
+            .get(format!("{}/models", self.base_url))
+            .send()
+            .await
+            .context("Failed to list models")?;
+
+        let status = response.status();
+        let headers = response.headers().clone();
+        let body = response.text().await.context("Failed to read model list")?;
+        if !status.is_success() {
+            return Err(parse_error(status, &headers, &body).into());
+        }
+        parse_models(&body)
+    }
+}
+
+// Only the parts of the chat completion responses qq uses. Parsing them
+// leniently keeps OpenAI-compatible servers with partial responses working,
+// and lets us read fields some providers add, like OpenRouter's cost.
+
+#[derive(Deserialize)]
+pub(crate) struct ChatResponse {
+    #[serde(default)]
+    choices: Vec<ChatChoice>,
+    usage: Option<UsageResponse>,
+}
+
+#[derive(Deserialize)]
+struct ChatChoice {
+    message: ChatMessage,
+}
+
+#[derive(Deserialize)]
+struct ChatMessage {
+    content: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub(crate) struct ChatChunk {
+    #[serde(default)]
+    choices: Vec<ChatChunkChoice>,
+    usage: Option<UsageResponse>,
+}
+
+#[derive(Deserialize)]
+struct ChatChunkChoice {
+    delta: ChatMessage,
+}
+
+#[derive(Deserialize)]
+struct ErrorResponse {
+    error: ErrorDetails,
+}
+
+#[derive(Deserialize)]
+struct ErrorDetails {
+    message: String,
+}
+
+#[derive(Deserialize)]
+struct ModelsResponse {
+    data: Vec<ModelEntry>,
+}
+
+#[derive(Deserialize)]
+struct ModelEntry {
+    id: String,
+    context_length: Option<u32>,
+    pricing: Option<ModelPricing>,
+}
+
+/// OpenRouter prices are USD per token, as strings
+#[derive(Deserialize)]
+struct ModelPricing {
+    prompt: String,
+    completion: String,
+}
+
+impl From<ModelEntry> for ModelInfo {
+    fn from(entry: ModelEntry) -> Self {
+        let price = entry.pricing.and_then(|pricing| {
+            Some(ModelPrice {
+                prompt: pricing.prompt.parse::<f64>().ok()? * 1_000_000.0,
+                completion: pricing.completion.parse::<f64>().ok()? * 1_000_000.0,
+            })
+        });
+        Self {
+            id: entry.id,
+            context_length: entry.context_length,
+            price,
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct UsageResponse {
+    prompt_tokens: u32,
+    completion_tokens: u32,
+    cost: Option<f64>,
+}
+
+impl From<UsageResponse> for TokenUsage {
+    fn from(usage: UsageResponse) -> Self {
+        Self {
+            prompt_tokens: usage.prompt_tokens,
+            completion_tokens: usage.completion_tokens,
+            cost: usage.cost,
+        }
+    }
+}
+
+/// Builds a chat completion request. Streamed requests ask for the token usage
+/// to be sent at the end of the stream.
 pub(crate) fn build_openai_request(
     model: &str,
+    temperature: Option<f32>,
     system_prompt: &str,
-    user_prompt: &str,
+    history: &[Message],
+    stream: bool,
 ) -> Result<CreateChatCompletionRequest> {
     let system_message = ChatCompletionRequestSystemMessageArgs::default()
         .content(system_prompt)
         .build()?;
 
-    let user_message = ChatCompletionRequestUserMessageArgs::default()
-        .content(user_prompt)
-        .build()?;
+    let mut messages = vec![ChatCompletionRequestMessage::System(system_message)];
+    for message in history {
+        let message = match message.role {
+            Role::User => ChatCompletionRequestUserMessageArgs::default()
+                .content(message.content.as_str())
+                .build()?
+                .into(),
+            Role::Assistant => ChatCompletionRequestAssistantMessageArgs::default()
+                .content(message.content.as_str())
+                .build()?
+                .into(),
+        };
+        messages.push(message);
+    }
 
-    let messages = vec![
-        ChatCompletionRequestMessage::System(system_message),
-        ChatCompletionRequestMessage::User(user_message),
-    ];
+    let mut request_args = CreateChatCompletionRequestArgs::default();
+    request_args.model(model).messages(messages);
+    if let Some(temperature) = temperature {
+        request_args.temperature(temperature);
+    }
+    if stream {
+        request_args
+            .stream(true)
+            .stream_options(ChatCompletionStreamOptions {
+                include_usage: true,
+            });
+    }
+    let request = request_args.build().context("Failed to build request")?;
 
-    let request = CreateChatCompletionRequestArgs::default()
-        .model(model)
-        .messages(messages)
+    Ok(request)
+}
+
+/// Sends a chat completion request and waits for the whole answer
+pub(crate) async fn complete(
+    client: &ChatClient,
+    request: impl Serialize + Send + Sync,
+) -> Result<Completion> {
+    let response: ChatResponse = client
+        .send(&request)
+        .await?
+        .json()
+        .await
+        .context("Failed to parse response")?;
+
+    let content = response
+        .choices
+        .into_iter()
+        .next()
+        .and_then(|choice| choice.message.content)
+        .ok_or_else(|| anyhow!("Response is empty or contains no content"))?;
+
+    Ok(Completion {
+        content,
+        usage: response.usage.map(TokenUsage::from),
+    })
+}
+
+/// Sends a streamed chat completion request
+pub(crate) async fn complete_stream(
+    client: &ChatClient,
+    request: impl Serialize + Send + Sync,
+) -> Result<ResponseStream> {
+    let chunks = client
+        .send(&request)
+        .await?
+        .bytes_stream()
+        .eventsource()
+        // The stream ends with a `[DONE]` marker rather than a chunk
+        .take_while(|event| {
+            let done = matches!(event, Ok(event) if event.data == "[DONE]");
+            futures::future::ready(!done)
+        })
+        .map(|event| match event {
+            Ok(event) => serde_json::from_str(&event.data).context("Failed to parse stream chunk"),
+            Err(err) => Err(anyhow!("Failed to read response stream: {}", err)),
+        });
+
+    Ok(into_response_stream(chunks))
+}
+
+/// An HTTP client giving up when the provider takes longer than `timeout` to
+/// accept the connection, start answering or send the next part of the answer.
+/// Long answers that keep arriving aren't cut off.
+pub(crate) fn http_client(headers: HeaderMap, timeout: Duration) -> reqwest::Client {
+    reqwest::Client::builder()
+        .default_headers(headers)
+        .connect_timeout(timeout)
+        .read_timeout(timeout)
         .build()
-        .context("Failed to build request")?;
+        .unwrap_or_default()
+}
 
-    Ok(request)
+/// Turns the `headers` of a `[providers.*]` section into request headers
+pub(crate) fn parse_headers(headers: &HashMap<String, String>) -> Result<HeaderMap> {
+    let mut header_map = HeaderMap::new();
+    for (name, value) in headers {
+        let header_name = HeaderName::from_bytes(name.as_bytes())
+            .context(format!("Invalid header name '{}'", name))?;
+        let header_value =
+            HeaderValue::from_str(value).context(format!("Invalid value for header '{}'", name))?;
+        header_map.insert(header_name, header_value);
+    }
+    Ok(header_map)
+}
+
+/// Parses a `{ "data": [{ "id": ... }] }` model list, as served by OpenAI,
+/// OpenRouter, Anthropic and most OpenAI-compatible servers
+pub(crate) fn parse_models(body: &str) -> Result<Vec<ModelInfo>> {
+    let response: ModelsResponse =
+        serde_json::from_str(body).context("Failed to parse model list")?;
+    Ok(response.data.into_iter().map(ModelInfo::from).collect())
+}
+
+/// Uses the message of an OpenAI-style error body when there is one
+fn parse_error(status: reqwest::StatusCode, headers: &HeaderMap, body: &str) -> ApiError {
+    let message = match serde_json::from_str::<ErrorResponse>(body) {
+        Ok(error) => error.error.message,
+        Err(_) => body.trim().to_string(),
+    };
+    ApiError::new(
+        status,
+        headers,
+        format!("Request failed with status {}: {}", status, message),
+    )
+}
+
+/// Maps a chat completion chunk stream into content deltas and usage,
+/// skipping chunks that carry neither (e.g. role-only chunks)
+fn into_response_stream(
+    stream: impl Stream<Item = Result<ChatChunk>> + Send + 'static,
+) -> ResponseStream {
+    stream
+        .flat_map(|chunk| {
+            let events: Vec<Result<StreamEvent>> = match chunk {
+                Ok(chunk) => {
+                    let delta = chunk
+                        .choices
+                        .into_iter()
+                        .next()
+                        .and_then(|choice| choice.delta.content)
+                        .filter(|content| !content.is_empty())
+                        .map(StreamEvent::Delta);
+                    let usage = chunk.usage.map(|usage| StreamEvent::Usage(usage.into()));
+                    delta.into_iter().chain(usage).map(Ok).collect()
+                }
+                Err(err) => vec![Err(err)],
+            };
+            futures::stream::iter(events)
+        })
+        .boxed()
 }
 
 #[cfg(test)]
 #[cfg_attr(coverage_nightly, coverage(off))]
 mod tests {
     use async_openai::types::{
-        ChatCompletionRequestSystemMessageContent, ChatCompletionRequestUserMessageContent,
+        ChatCompletionRequestAssistantMessageContent, ChatCompletionRequestSystemMessageContent,
+        ChatCompletionRequestUserMessageContent,
     };
+    use futures::{TryStreamExt, stream};
 
     use super::*;
 
@@ -47,10 +359,19 @@ mod tests {
         let system_prompt = "You are a helpful assistant";
         let user_prompt = "What is 2+2?";
 
-        let request = build_openai_request(model, system_prompt, user_prompt).unwrap();
+        let request = build_openai_request(
+            model,
+            None,
+            system_prompt,
+            &[Message::user(user_prompt)],
+            false,
+        )
+        .unwrap();
 
         // Verify model is set correctly
         assert_eq!(request.model, "gpt-3.5-turbo");
+        assert_eq!(request.temperature, None);
+        assert_eq!(request.stream, None);
 
         // Verify messages structure
         assert_eq!(request.messages.len(), 2);
@@ -77,4 +398,234 @@ mod tests {
             _ => panic!("Second message should be a user message"),
         }
     }
+
+    #[test]
+    fn test_build_openai_request_with_temperature() {
+        let request = build_openai_request(
+            "gpt-4o",
+            Some(0.2),
+            "system",
+            &[Message::user("user")],
+            false,
+        )
+        .unwrap();
+        assert_eq!(request.temperature, Some(0.2));
+    }
+
+    #[test]
+    fn test_build_openai_request_with_history() {
+        let history = [
+            Message::user("list files"),
+            Message::assistant("ls"),
+            Message::user("now include hidden ones"),
+        ];
+
+        let request = build_openai_request("gpt-4o", None, "system", &history, false).unwrap();
+
+        assert_eq!(request.messages.len(), 4);
+        assert!(matches!(
+            request.messages[1],
+            ChatCompletionRequestMessage::User(_)
+        ));
+        match &request.messages[2] {
+            ChatCompletionRequestMessage::Assistant(msg) => match &msg.content {
+                Some(ChatCompletionRequestAssistantMessageContent::Text(text)) => {
+                    assert_eq!(text, "ls");
+                }
+                _ => panic!("Assistant message content should be text"),
+            },
+            _ => panic!("Third message should be an assistant message"),
+        }
+        assert!(matches!(
+            request.messages[3],
+            ChatCompletionRequestMessage::User(_)
+        ));
+    }
+
+    #[test]
+    fn test_build_openai_request_for_stream() {
+        let request =
+            build_openai_request("gpt-4o", None, "system", &[Message::user("user")], true).unwrap();
+        assert_eq!(request.stream, Some(true));
+        assert_eq!(
+            request.stream_options,
+            Some(ChatCompletionStreamOptions {
+                include_usage: true
+            })
+        );
+    }
+
+    #[test]
+    fn test_parse_response_with_cost() {
+        let response: ChatResponse = serde_json::from_str(
+            r#"{
+                "id": "gen-1",
+                "choices": [{ "index": 0, "message": { "role": "assistant", "content": "ls" } }],
+                "usage": { "prompt_tokens": 30, "completion_tokens": 2, "total_tokens": 32, "cost": 0.00012 }
+            }"#,
+        )
+        .unwrap();
+
+        assert_eq!(response.choices[0].message.content.as_deref(), Some("ls"));
+        assert_eq!(
+            response.usage.map(TokenUsage::from),
+            Some(TokenUsage {
+                prompt_tokens: 30,
+                completion_tokens: 2,
+                cost: Some(0.00012),
+            })
+        );
+    }
+
+    #[test]
+    fn test_parse_error() {
+        let body = r#"{"error":{"message":"Rate limit exceeded: free-models-per-min","code":429}}"#;
+        let error = parse_error(
+            reqwest::StatusCode::TOO_MANY_REQUESTS,
+            &HeaderMap::new(),
+            body,
+        );
+        assert!(error.is_retryable());
+        assert_eq!(
+            error.to_string(),
+            "Request failed with status 429 Too Many Requests: Rate limit exceeded: free-models-per-min"
+        );
+
+        let error = parse_error(
+            reqwest::StatusCode::BAD_GATEWAY,
+            &HeaderMap::new(),
+            "<html>Bad gateway</html>\n",
+        );
+        assert_eq!(
+            error.to_string(),
+            "Request failed with status 502 Bad Gateway: <html>Bad gateway</html>"
+        );
+    }
+
+    #[test]
+    fn test_parse_headers() {
+        let headers = parse_headers(&HashMap::from([
+            ("X-Team".to_string(), "platform".to_string()),
+            ("x-gateway-route".to_string(), "llm".to_string()),
+        ]))
+        .unwrap();
+
+        assert_eq!(headers.len(), 2);
+        assert_eq!(headers.get("x-team").unwrap(), "platform");
+        assert_eq!(headers.get("x-gateway-route").unwrap(), "llm");
+    }
+
+    #[test]
+    fn test_parse_headers_rejects_invalid_name() {
+        let result = parse_headers(&HashMap::from([(
+            "bad header".to_string(),
+            "value".to_string(),
+        )]));
+
+        assert!(result.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_stalled_response_times_out() {
+        // Accepts connections but never answers
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let base_url = format!("http://{}", listener.local_addr().unwrap());
+        let client = ChatClient::new("", &base_url, HeaderMap::new(), Duration::from_millis(50));
+
+        let err = client.models().await.unwrap_err();
+        assert!(crate::retry::is_retryable(&err));
+    }
+
+    #[test]
+    fn test_parse_models() {
+        let openrouter = r#"{"data":[{"id":"anthropic/claude-sonnet-4","name":"Claude Sonnet 4","context_length":200000,"pricing":{"prompt":"0.000003","completion":"0.000015"}}]}"#;
+        let models = parse_models(openrouter).unwrap();
+        assert_eq!(models[0].id, "anthropic/claude-sonnet-4");
+        assert_eq!(models[0].context_length, Some(200000));
+        let price = models[0].price.unwrap();
+        assert!((price.prompt - 3.0).abs() < 1e-9);
+        assert!((price.completion - 15.0).abs() < 1e-9);
+
+        let openai =
+            r#"{"object":"list","data":[{"id":"gpt-4o","object":"model","owned_by":"system"}]}"#;
+        assert_eq!(
+            parse_models(openai).unwrap(),
+            vec![ModelInfo {
+                id: "gpt-4o".to_string(),
+                context_length: None,
+                price: None,
+            }]
+        );
+
+        assert!(parse_models("<html>").is_err());
+    }
+
+    fn stream_chunk(content: Option<&str>) -> ChatChunk {
+        serde_json::from_value(serde_json::json!({
+            "id": "chunk",
+            "choices": [{
+                "index": 0,
+                "delta": { "content": content },
+                "finish_reason": null
+            }],
+            "created": 0,
+            "model": "gpt-3.5-turbo",
+            "object": "chat.completion.chunk"
+        }))
+        .unwrap()
+    }
+
+    fn usage_chunk() -> ChatChunk {
+        serde_json::from_value(serde_json::json!({
+            "id": "chunk",
+            "choices": [],
+            "usage": { "prompt_tokens": 12, "completion_tokens": 3, "total_tokens": 15 }
+        }))
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_into_response_stream_yields_content_deltas() {
+        let chunks: Vec<Result<ChatChunk>> = vec![
+            Ok(stream_chunk(None)),
+            Ok(stream_chunk(Some("git "))),
+            Ok(stream_chunk(Some(""))),
+            Ok(stream_chunk(Some("status"))),
+            Ok(usage_chunk()),
+        ];
+
+        let events: Vec<StreamEvent> = into_response_stream(stream::iter(chunks))
+            .try_collect()
+            .await
+            .unwrap();
+
+        assert_eq!(
+            events,
+            vec![
+                StreamEvent::Delta("git ".to_string()),
+                StreamEvent::Delta("status".to_string()),
+                StreamEvent::Usage(TokenUsage {
+                    prompt_tokens: 12,
+                    completion_tokens: 3,
+                    cost: None,
+                }),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_into_response_stream_propagates_errors() {
+        let chunks: Vec<Result<ChatChunk>> = vec![
+            Ok(stream_chunk(Some("ls"))),
+            Err(anyhow!("connection reset")),
+        ];
+
+        let mut stream = into_response_stream(stream::iter(chunks));
+
+        assert_eq!(
+            stream.next().await.unwrap().unwrap(),
+            StreamEvent::Delta("ls".to_string())
+        );
+        assert!(stream.next().await.unwrap().is_err());
+    }
 }
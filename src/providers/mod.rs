@@ -1,6 +1,134 @@
+mod anthropic;
 mod helpers;
 mod open_router;
-mod openai;
+mod openai_compatible;
 
+use std::time::Duration;
+
+pub use anthropic::Anthropic;
+use anyhow::{Result, anyhow};
+use async_openai::config::OPENAI_API_BASE;
+pub(crate) use helpers::build_openai_request;
+use helpers::parse_headers;
 pub use open_router::OpenRouter;
-pub use openai::OpenAI;
+pub use openai_compatible::OpenAICompatible;
+
+use crate::{
+    configs::Config,
+    provider::{LLMProvider, Provider},
+};
+
+// Ollama serves an OpenAI-compatible API under /v1
+const OLLAMA_API_BASE: &str = "http://localhost:11434/v1";
+
+/// Instantiates the provider selected in the config
+pub fn create_provider(config: &Config) -> Result<Box<dyn LLMProvider>> {
+    let api_key = &config.api_key;
+    let model = &config.model;
+    let base_url = config.base_url.as_deref();
+    let headers = parse_headers(&config.headers).map_err(|err| {
+        anyhow!(
+            "Invalid headers for provider '{}': {:#}",
+            config.provider,
+            err
+        )
+    })?;
+
+    let temperature = config.persona.temperature;
+    let timeout = Duration::from_secs(config.retry.timeout_secs);
+
+    let provider: Box<dyn LLMProvider> = match &config.provider {
+        Provider::OpenRouter => {
+            let provider = match base_url {
+                Some(base_url) => {
+                    OpenRouter::with_base_url(api_key, model, base_url, headers, timeout)
+                }
+                None => OpenRouter::new(api_key, model, headers, timeout),
+            };
+            Box::new(provider.with_temperature(temperature))
+        }
+        Provider::OpenAI => {
+            let base_url = base_url.unwrap_or(OPENAI_API_BASE);
+            let provider = OpenAICompatible::new(api_key, model, base_url, headers, timeout);
+            Box::new(provider.with_temperature(temperature))
+        }
+        Provider::Anthropic => {
+            let provider = match base_url {
+                Some(base_url) => {
+                    Anthropic::with_base_url(api_key, model, base_url, headers, timeout)
+                }
+                None => Anthropic::new(api_key, model, headers, timeout),
+            };
+            Box::new(provider.with_temperature(temperature))
+        }
+        Provider::Ollama => {
+            // Ollama doesn't check API keys
+            let base_url = base_url.unwrap_or(OLLAMA_API_BASE);
+            let provider = OpenAICompatible::new("", model, base_url, headers, timeout);
+            Box::new(provider.with_temperature(temperature))
+        }
+        Provider::Custom(name) => {
+            let base_url = base_url.ok_or_else(|| {
+                anyhow!(
+                    "No base_url set for provider '{}'\nAdd 'base_url = \"http://...\"' to the [providers.{}] section",
+                    name,
+                    name
+                )
+            })?;
+            let provider = OpenAICompatible::new(api_key, model, base_url, headers, timeout);
+            Box::new(provider.with_temperature(temperature))
+        }
+    };
+
+    Ok(provider)
+}
+
+#[cfg(test)]
+#[cfg_attr(coverage_nightly, coverage(off))]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+
+    fn create_custom_config(base_url: Option<&str>) -> Config {
+        Config {
+            provider: Provider::Custom("vllm".to_string()),
+            model: "qwen2.5-coder".to_string(),
+            base_url: base_url.map(String::from),
+            headers: HashMap::from([("x-team".to_string(), "platform".to_string())]),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_create_custom_provider() {
+        let config = create_custom_config(Some("http://localhost:8000/v1"));
+        assert!(create_provider(&config).is_ok());
+    }
+
+    #[test]
+    fn test_create_custom_provider_without_base_url() {
+        let config = create_custom_config(None);
+        assert!(create_provider(&config).is_err());
+    }
+
+    #[test]
+    fn test_builtin_providers_use_headers() {
+        for provider in [
+            Provider::OpenRouter,
+            Provider::OpenAI,
+            Provider::Anthropic,
+            Provider::Ollama,
+        ] {
+            let mut config = Config {
+                provider,
+                headers: HashMap::from([("x-team".to_string(), "platform".to_string())]),
+                ..Default::default()
+            };
+            assert!(create_provider(&config).is_ok());
+
+            config.headers = HashMap::from([("bad header".to_string(), "value".to_string())]);
+            assert!(create_provider(&config).is_err());
+        }
+    }
+}
@@ -1,38 +1,51 @@
-use anyhow::{Context, anyhow};
-use async_openai::{Client, config::OpenAIConfig};
+use std::time::Duration;
+
+use async_openai::types::CreateChatCompletionRequest;
 use async_trait::async_trait;
 use reqwest::header::{HeaderMap, HeaderName, HeaderValue};
+use serde::Serialize;
 
-use crate::{provider::LLMProvider, providers::helpers::build_openai_request};
+use crate::{
+    provider::{Completion, LLMProvider, Message, ModelInfo, ResponseStream},
+    providers::helpers::{ChatClient, build_openai_request, complete, complete_stream},
+};
 
 const OPEN_ROUTER_API_BASE: &str = "https://openrouter.ai/api/v1";
 
 pub struct OpenRouter {
-    client: Client<OpenAIConfig>,
+    client: ChatClient,
     model: String,
+    temperature: Option<f32>,
 }
 
 impl OpenRouter {
-    pub fn new(api_key: &str, model: &str) -> Self {
-        Self::with_base_url(api_key, model, OPEN_ROUTER_API_BASE)
+    pub fn new(api_key: &str, model: &str, headers: HeaderMap, timeout: Duration) -> Self {
+        Self::with_base_url(api_key, model, OPEN_ROUTER_API_BASE, headers, timeout)
     }
 
-    fn with_base_url(api_key: &str, model: &str, base_url: &str) -> Self {
-        let headers = Self::get_headers();
-        let http_client = reqwest::Client::builder()
-            .default_headers(headers)
-            .build()
-            .unwrap_or_default();
-        let config = OpenAIConfig::new()
-            .with_api_base(base_url)
-            .with_api_key(api_key);
-        let client = Client::with_config(config).with_http_client(http_client);
+    /// Configured `headers` replace the default ones of the same name
+    pub fn with_base_url(
+        api_key: &str,
+        model: &str,
+        base_url: &str,
+        headers: HeaderMap,
+        timeout: Duration,
+    ) -> Self {
+        let mut all_headers = Self::get_headers();
+        all_headers.extend(headers);
+        let client = ChatClient::new(api_key, base_url, all_headers, timeout);
         Self {
             client,
             model: model.to_string(),
+            temperature: None,
         }
     }
 
+    pub fn with_temperature(mut self, temperature: Option<f32>) -> Self {
+        self.temperature = temperature;
+        self
+    }
+
     fn get_headers() -> HeaderMap {
         let mut headers = HeaderMap::new();
         headers.insert(
@@ -47,31 +60,64 @@ impl OpenRouter {
     }
 }
 
+/// A chat completion request asking OpenRouter to include the cost in the
+/// reported usage
+#[derive(Serialize)]
+struct UsageAccountingRequest {
+    #[serde(flatten)]
+    request: CreateChatCompletionRequest,
+    usage: UsageAccounting,
+}
+
+#[derive(Serialize)]
+struct UsageAccounting {
+    include: bool,
+}
+
+fn with_usage_accounting(request: CreateChatCompletionRequest) -> UsageAccountingRequest {
+    UsageAccountingRequest {
+        request,
+        usage: UsageAccounting { include: true },
+    }
+}
+
 #[async_trait]
 impl LLMProvider for OpenRouter {
-    async fn prompt(&self, system_prompt: &str, user_prompt: &str) -> anyhow::Result<String> {
-        let request = build_openai_request(&self.model, system_prompt, user_prompt)?;
-
-        let response = self
-            .client
-            .chat()
-            .create(request)
-            .await
-            .context("Failed to get response")?;
-
-        response
-            .choices
-            .first()
-            .and_then(|first| first.message.content.as_ref())
-            .map(|content| content.to_string())
-            .ok_or_else(|| anyhow!("Response is empty or contains no content"))
+    async fn prompt(
+        &self,
+        system_prompt: &str,
+        messages: &[Message],
+    ) -> anyhow::Result<Completion> {
+        let request = build_openai_request(
+            &self.model,
+            self.temperature,
+            system_prompt,
+            messages,
+            false,
+        )?;
+        complete(&self.client, with_usage_accounting(request)).await
+    }
+
+    async fn prompt_stream(
+        &self,
+        system_prompt: &str,
+        messages: &[Message],
+    ) -> anyhow::Result<ResponseStream> {
+        let request =
+            build_openai_request(&self.model, self.temperature, system_prompt, messages, true)?;
+        complete_stream(&self.client, with_usage_accounting(request)).await
+    }
+
+    async fn models(&self) -> anyhow::Result<Vec<ModelInfo>> {
+        self.client.models().await
     }
 }
 
 #[cfg(test)]
 #[cfg_attr(coverage_nightly, coverage(off))]
 mod tests {
-    use super::OpenRouter;
+    use super::*;
+    use crate::providers::helpers::build_openai_request;
 
     #[test]
     fn test_get_headers() {
@@ -83,4 +129,21 @@ mod tests {
         );
         assert_eq!(headers.get("x-title").unwrap(), "qq");
     }
+
+    #[test]
+    fn test_request_includes_usage_accounting() {
+        let request = build_openai_request(
+            "openai/gpt-4o",
+            None,
+            "system",
+            &[Message::user("hi")],
+            true,
+        )
+        .unwrap();
+        let json = serde_json::to_value(with_usage_accounting(request)).unwrap();
+
+        assert_eq!(json["model"], "openai/gpt-4o");
+        assert_eq!(json["stream"], true);
+        assert_eq!(json["usage"], serde_json::json!({ "include": true }));
+    }
 }
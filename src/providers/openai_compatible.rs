@@ -0,0 +1,69 @@
+use std::time::Duration;
+
+use anyhow::Result;
+use async_trait::async_trait;
+use reqwest::header::HeaderMap;
+
+use crate::{
+    provider::{Completion, LLMProvider, Message, ModelInfo, ResponseStream},
+    providers::helpers::{ChatClient, build_openai_request, complete, complete_stream},
+};
+
+/// Any server speaking the OpenAI chat completions protocol: OpenAI itself,
+/// Ollama and custom providers (vLLM, LM Studio, internal gateways, ...).
+/// An empty API key sends no `Authorization` header.
+pub struct OpenAICompatible {
+    client: ChatClient,
+    model: String,
+    temperature: Option<f32>,
+}
+
+impl OpenAICompatible {
+    pub fn new(
+        api_key: &str,
+        model: &str,
+        base_url: &str,
+        headers: HeaderMap,
+        timeout: Duration,
+    ) -> Self {
+        let client = ChatClient::new(api_key, base_url, headers, timeout);
+        Self {
+            client,
+            model: model.to_string(),
+            temperature: None,
+        }
+    }
+
+    pub fn with_temperature(mut self, temperature: Option<f32>) -> Self {
+        self.temperature = temperature;
+        self
+    }
+}
+
+#[async_trait]
+impl LLMProvider for OpenAICompatible {
+    async fn prompt(&self, system_prompt: &str, messages: &[Message]) -> Result<Completion> {
+        let request = build_openai_request(
+            &self.model,
+            self.temperature,
+            system_prompt,
+            messages,
+            false,
+        )?;
+        complete(&self.client, request).await
+    }
+
+    async fn prompt_stream(
+        &self,
+        system_prompt: &str,
+        messages: &[Message],
+    ) -> Result<ResponseStream> {
+        let request =
+            build_openai_request(&self.model, self.temperature, system_prompt, messages, true)?;
+        complete_stream(&self.client, request).await
+    }
+
+    async fn models(&self) -> Result<Vec<ModelInfo>> {
+        self.client.models().await
+    }
+}
@@ -0,0 +1,207 @@
+use std::{
+    io::{self, IsTerminal, Write},
+    time::Instant,
+};
+
+use arboard::Clipboard;
+use clap::ValueEnum;
+use futures::StreamExt;
+use spinoff::{Color, Spinner, Streams, spinners};
+
+use crate::{
+    cache::ResponseCache,
+    configs::Config,
+    logging::RequestLogEntryBuilder,
+    pricing::request_cost,
+    prompts::get_system_prompt,
+    provider::{LLMProvider, Message, ResponseStream, StreamEvent, TokenUsage},
+    retry::with_retries,
+    safety::analyze,
+    system_info::SystemInfo,
+};
+
+/// How `qq` prints answers
+#[derive(ValueEnum, Clone, Copy, Debug, Default, PartialEq)]
+pub enum OutputFormat {
+    #[default]
+    Text,
+    /// The request log entry as one JSON object, without spinner or colours
+    Json,
+}
+
+/// Sends the conversation to the model and prints the answer, recording the
+/// outcome in the log entry. Errors are left to the caller to report. With
+/// JSON output nothing is printed, as the caller prints the log entry.
+/// Retried attempts are added to `retries`, which the log records, so it can
+/// count them across several models.
+pub async fn ask(
+    provider: &dyn LLMProvider,
+    config: &Config,
+    messages: &[Message],
+    cache: Option<&ResponseCache>,
+    retries: &mut u32,
+    log_entry: &mut RequestLogEntryBuilder,
+) -> anyhow::Result<String> {
+    let quiet = config.output == OutputFormat::Json;
+    // When the output is captured, e.g. by the shell widget, keep it clean and
+    // show progress on stderr instead
+    let interactive = io::stdout().is_terminal() && !quiet;
+    let stream = if interactive {
+        Streams::Stdout
+    } else {
+        Streams::Stderr
+    };
+    let mut spinner = (!quiet).then(|| {
+        Spinner::new_with_stream(
+            spinners::Dots,
+            format!("Asking {}", config.model),
+            Color::Blue,
+            stream,
+        )
+    });
+    let system_info = SystemInfo::detect(&config.system);
+    let system_prompt = get_system_prompt(&config.persona, &system_info);
+
+    let llm_start = Instant::now();
+    let mut response = String::new();
+    let mut usage = None;
+    let mut model_retries = 0;
+    let cache_key = cache.and_then(|_| ResponseCache::key(config, &system_prompt, messages).ok());
+    let cached = cache
+        .zip(cache_key.as_deref())
+        .and_then(|(cache, key)| cache.get(key));
+    let from_cache = cached.is_some();
+    log_entry.cached(from_cache);
+    let result = if let Some(cached) = cached {
+        if let Some(mut spinner) = spinner.take() {
+            spinner.clear();
+        }
+        if !quiet {
+            print!("{}", cached);
+        }
+        response = cached;
+        Ok(())
+    } else if interactive {
+        // Only starting the stream is retried, as parts of the answer may
+        // already be printed once it fails
+        match with_retries(&config.retry, &mut model_retries, || {
+            provider.prompt_stream(&system_prompt, messages)
+        })
+        .await
+        {
+            Ok(stream) => stream_response(stream, &mut spinner, &mut response, &mut usage).await,
+            Err(err) => Err(err),
+        }
+    } else {
+        // Nobody is watching the output as it arrives, so wait for the full answer
+        with_retries(&config.retry, &mut model_retries, || {
+            provider.prompt(&system_prompt, messages)
+        })
+        .await
+        .map(|completion| {
+            if let Some(mut spinner) = spinner.take() {
+                spinner.clear();
+            }
+            if !quiet {
+                print!("{}", completion.content);
+            }
+            response = completion.content;
+            usage = completion.usage;
+        })
+    };
+
+    let llm_duration = llm_start.elapsed();
+    log_entry.llm_response_time_ms(llm_duration.as_millis() as u64);
+    *retries += model_retries;
+    log_entry.retries(*retries);
+    log_entry.response(config.redactor.redact(&response).0);
+    log_entry.usage(usage);
+    log_entry.cost_usd(usage.and_then(|usage| request_cost(&usage, &config.model, &config.prices)));
+    if let Some(mut spinner) = spinner.take() {
+        spinner.clear();
+    }
+
+    match result {
+        Ok(()) => {
+            // Storing a cached answer again would restart its TTL
+            if let (Some(cache), Some(key)) = (cache, &cache_key)
+                && !from_cache
+                && let Err(err) = cache.put(key, &response)
+            {
+                eprintln!("{}", err);
+            }
+
+            let warnings = analyze(&response, &config.safety_rules);
+            let copy = config.auto_copy && (warnings.is_empty() || config.force_copy);
+            if copy && copy_to_clipboard(&response) && interactive {
+                print!(" \x1b[90m(copied)\x1b[0m");
+            }
+            // JSON output carries the warnings in `safety_warnings`
+            if !quiet {
+                println!();
+                for warning in &warnings {
+                    eprintln!("\x1b[1;31mWarning:\x1b[0m \x1b[31m{warning}\x1b[0m");
+                }
+            }
+            if !copy && config.auto_copy {
+                eprintln!("\x1b[90mNot copied, use --force-copy to copy anyway\x1b[0m");
+            }
+            log_entry.safety_warnings(warnings.into_iter().map(String::from).collect::<Vec<_>>());
+            Ok(response)
+        }
+        Err(err) => {
+            // End the partial answer before the error is shown
+            if !response.is_empty() {
+                println!();
+            }
+            Err(err)
+        }
+    }
+}
+
+/// Hides secrets in the prompt before it leaves the machine, letting the user
+/// know when something was replaced
+pub fn redact_prompt(config: &Config, prompt: &str) -> String {
+    let (redacted, count) = config.redactor.redact(prompt);
+    if count > 0 && config.output == OutputFormat::Text {
+        eprintln!(
+            "\x1b[90m(redacted {} secret{} from the prompt, use --no-redact to send it as is)\x1b[0m",
+            count,
+            if count == 1 { "" } else { "s" }
+        );
+    }
+    redacted
+}
+
+/// Prints response deltas as they arrive, clearing the spinner on the first
+/// one, and adds up the reported usage
+async fn stream_response(
+    mut stream: ResponseStream,
+    spinner: &mut Option<Spinner>,
+    response: &mut String,
+    usage: &mut Option<TokenUsage>,
+) -> anyhow::Result<()> {
+    while let Some(event) = stream.next().await {
+        match event? {
+            StreamEvent::Delta(delta) => {
+                if let Some(mut spinner) = spinner.take() {
+                    spinner.clear();
+                }
+                print!("{delta}");
+                io::stdout().flush()?;
+                response.push_str(&delta);
+            }
+            StreamEvent::Usage(reported) => {
+                *usage = Some(usage.unwrap_or_default() + reported);
+            }
+        }
+    }
+    Ok(())
+}
+
+pub fn copy_to_clipboard(text: &str) -> bool {
+    match Clipboard::new() {
+        Ok(mut cb) => cb.set_text(text).is_ok(),
+        Err(_) => false,
+    }
+}
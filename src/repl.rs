@@ -0,0 +1,251 @@
+use std::time::Instant;
+
+use anyhow::{Context, Result};
+use chrono::Local;
+use rustyline::{DefaultEditor, error::ReadlineError};
+
+use crate::{
+    args::Args,
+    configs::{Config, ProdConfigService},
+    logging::{RequestLogEntryBuilder, write_log_entry},
+    provider::{LLMProvider, Message},
+    providers::create_provider,
+    query::{ask, copy_to_clipboard, redact_prompt},
+};
+
+const PROMPT: &str = "qq> ";
+
+const HELP: &str = "\
+/model [NAME]    Show or switch the model
+/persona [NAME]  Show or switch the persona
+/copy            Copy the last response to the clipboard
+/clear           Forget the conversation so far
+/help            Show this help
+/exit            Leave the chat (or press Ctrl-D)";
+
+/// A line starting with `/` typed into the chat
+#[derive(Debug, PartialEq)]
+enum ReplCommand {
+    Model(Option<String>),
+    Persona(Option<String>),
+    Copy,
+    Clear,
+    Help,
+    Exit,
+    Unknown(String),
+}
+
+impl ReplCommand {
+    /// Returns the command on the line, or nothing if it's a question
+    fn parse(line: &str) -> Option<Self> {
+        let line = line.strip_prefix('/')?;
+        let (name, argument) = match line.split_once(char::is_whitespace) {
+            Some((name, argument)) => (name, Some(argument.trim().to_string())),
+            None => (line, None),
+        };
+        let argument = argument.filter(|argument| !argument.is_empty());
+
+        let command = match name {
+            "model" => Self::Model(argument),
+            "persona" => Self::Persona(argument),
+            "copy" => Self::Copy,
+            "clear" => Self::Clear,
+            "help" => Self::Help,
+            "exit" | "quit" => Self::Exit,
+            _ => Self::Unknown(name.to_string()),
+        };
+        Some(command)
+    }
+}
+
+/// Interactive chat keeping the conversation in memory until it ends
+pub struct Repl<'a> {
+    args: Args,
+    config_service: &'a ProdConfigService,
+    config: Config,
+    provider: Box<dyn LLMProvider>,
+    messages: Vec<Message>,
+    last_response: Option<String>,
+}
+
+impl<'a> Repl<'a> {
+    pub fn new(args: &Args, config_service: &'a ProdConfigService, config: Config) -> Result<Self> {
+        let provider = create_provider(&config)?;
+        Ok(Self {
+            args: args.clone(),
+            config_service,
+            config,
+            provider,
+            messages: Vec::new(),
+            last_response: None,
+        })
+    }
+
+    pub async fn run(&mut self) -> Result<()> {
+        let mut editor = DefaultEditor::new().context("Failed to start line editor")?;
+        let history_path = self.config_service.get_home_path().join("chat_history");
+        // There's no history before the first chat
+        let _ = editor.load_history(&history_path);
+
+        println!(
+            "\x1b[90mChatting with {} as '{}'. Type /help for commands, Ctrl-D to exit.\x1b[0m",
+            self.config.model, self.config.persona.name
+        );
+
+        loop {
+            let line = match editor.readline(PROMPT) {
+                Ok(line) => line,
+                Err(ReadlineError::Interrupted) => continue,
+                Err(ReadlineError::Eof) => break,
+                Err(err) => return Err(err).context("Failed to read input"),
+            };
+
+            let line = line.trim();
+            if line.is_empty() {
+                continue;
+            }
+            let _ = editor.add_history_entry(line);
+
+            match ReplCommand::parse(line) {
+                Some(ReplCommand::Exit) => break,
+                Some(command) => self.handle_command(command),
+                None => self.ask(line).await,
+            }
+        }
+
+        if let Err(err) = editor.save_history(&history_path) {
+            eprintln!("Failed to save chat history: {}", err);
+        }
+        Ok(())
+    }
+
+    /// Sends one turn to the model and logs it as its own request
+    async fn ask(&mut self, question: &str) {
+        let mut log_entry = RequestLogEntryBuilder::default();
+        let turn_start = Instant::now();
+        log_entry.time(Local::now().to_rfc3339());
+        log_entry.config(&self.config);
+        let question = redact_prompt(&self.config, question);
+        log_entry.user_prompt(&question);
+
+        self.messages.push(Message::user(question));
+        match ask(
+            self.provider.as_ref(),
+            &self.config,
+            &self.messages,
+            None,
+            &mut 0,
+            &mut log_entry,
+        )
+        .await
+        {
+            Ok(response) => {
+                self.last_response = Some(response.clone());
+                self.messages.push(Message::assistant(response));
+            }
+            // Keep the conversation valid so the question can be asked again
+            Err(err) => {
+                log_entry.error(format!("{:?}", err));
+                eprintln!("{err:?}");
+                self.messages.pop();
+            }
+        }
+
+        write_log_entry(&mut log_entry, turn_start, self.config.log_file.as_ref());
+    }
+
+    fn handle_command(&mut self, command: ReplCommand) {
+        match command {
+            ReplCommand::Model(None) => println!("{}", self.config.model),
+            ReplCommand::Model(Some(model)) => {
+                self.args.model = Some(model);
+                self.reload();
+            }
+            ReplCommand::Persona(None) => println!("{}", self.config.persona.name),
+            ReplCommand::Persona(Some(persona)) => {
+                self.args.persona = Some(persona);
+                self.reload();
+            }
+            ReplCommand::Copy => match &self.last_response {
+                Some(response) if copy_to_clipboard(response) => {
+                    println!("\x1b[90m(copied)\x1b[0m")
+                }
+                Some(_) => println!("Failed to copy to the clipboard"),
+                None => println!("Nothing to copy yet"),
+            },
+            ReplCommand::Clear => {
+                self.messages.clear();
+                self.last_response = None;
+                println!("\x1b[90m(conversation cleared)\x1b[0m");
+            }
+            ReplCommand::Help => println!("{HELP}"),
+            ReplCommand::Unknown(name) => {
+                println!("Unknown command '/{}', type /help for commands", name)
+            }
+            ReplCommand::Exit => {}
+        }
+    }
+
+    /// Reloads the config with the switched model or persona and reconnects,
+    /// keeping the current setup if that fails
+    fn reload(&mut self) {
+        let result = self.config_service.load(&self.args).and_then(|config| {
+            let provider = create_provider(&config)?;
+            Ok((config, provider))
+        });
+
+        match result {
+            Ok((config, provider)) => {
+                self.config = config;
+                self.provider = provider;
+                println!(
+                    "\x1b[90mUsing {} as '{}'\x1b[0m",
+                    self.config.model, self.config.persona.name
+                );
+            }
+            Err(err) => {
+                self.args.model = Some(self.config.model.clone());
+                self.args.persona = Some(self.config.persona.name.clone());
+                eprintln!("{err:?}");
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+#[cfg_attr(coverage_nightly, coverage(off))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_question() {
+        assert_eq!(ReplCommand::parse("how do I list files"), None);
+    }
+
+    #[test]
+    fn test_parse_commands() {
+        assert_eq!(ReplCommand::parse("/copy"), Some(ReplCommand::Copy));
+        assert_eq!(ReplCommand::parse("/clear"), Some(ReplCommand::Clear));
+        assert_eq!(ReplCommand::parse("/quit"), Some(ReplCommand::Exit));
+        assert_eq!(
+            ReplCommand::parse("/frobnicate now"),
+            Some(ReplCommand::Unknown("frobnicate".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_parse_commands_with_argument() {
+        assert_eq!(
+            ReplCommand::parse("/model  openai/gpt-4o "),
+            Some(ReplCommand::Model(Some("openai/gpt-4o".to_string())))
+        );
+        assert_eq!(
+            ReplCommand::parse("/persona"),
+            Some(ReplCommand::Persona(None))
+        );
+        assert_eq!(
+            ReplCommand::parse("/model "),
+            Some(ReplCommand::Model(None))
+        );
+    }
+}
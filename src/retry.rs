@@ -0,0 +1,178 @@
+use std::{future::Future, time::Duration};
+
+use anyhow::Result;
+use tokio::time::sleep;
+
+use crate::{configs::RetryConfig, provider::ApiError};
+
+/// Runs a provider request, trying again after rate limits, server errors and
+/// timeouts with exponential backoff. `retries` counts the repeated attempts.
+/// The timeout itself is up to the provider's HTTP client.
+pub async fn with_retries<T, F, Fut>(
+    settings: &RetryConfig,
+    retries: &mut u32,
+    mut request: F,
+) -> Result<T>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T>>,
+{
+    loop {
+        let err = match request().await {
+            Ok(value) => return Ok(value),
+            Err(err) => err,
+        };
+
+        if *retries >= settings.max_retries || !is_retryable(&err) {
+            return Err(err);
+        }
+        let Some(delay) = retry_delay(settings, *retries, &err) else {
+            return Err(err);
+        };
+        sleep(delay).await;
+        *retries += 1;
+    }
+}
+
+/// Whether the request failed for a reason that may go away on its own
+pub fn is_retryable(err: &anyhow::Error) -> bool {
+    err.chain().any(|cause| {
+        if let Some(api_error) = cause.downcast_ref::<ApiError>() {
+            api_error.is_retryable()
+        } else if let Some(http_error) = cause.downcast_ref::<reqwest::Error>() {
+            http_error.is_timeout() || http_error.is_connect()
+        } else {
+            false
+        }
+    })
+}
+
+/// Waits as long as the provider asked, or backs off exponentially with
+/// jitter. Gives up when the provider asks for more than `max_backoff_ms`.
+fn retry_delay(settings: &RetryConfig, attempt: u32, err: &anyhow::Error) -> Option<Duration> {
+    let max_backoff = Duration::from_millis(settings.max_backoff_ms);
+    let retry_after = err
+        .chain()
+        .find_map(|cause| cause.downcast_ref::<ApiError>())
+        .and_then(|api_error| api_error.retry_after);
+    if let Some(retry_after) = retry_after {
+        return (retry_after <= max_backoff).then_some(retry_after);
+    }
+
+    let backoff = settings
+        .backoff_ms
+        .saturating_mul(1 << attempt.min(16))
+        .min(settings.max_backoff_ms);
+    // Spreading the waits keeps clients that failed together from retrying together
+    let jittered = backoff / 2 + fastrand::u64(0..=backoff / 2);
+    Some(Duration::from_millis(jittered))
+}
+
+#[cfg(test)]
+#[cfg_attr(coverage_nightly, coverage(off))]
+mod tests {
+    use std::cell::Cell;
+
+    use anyhow::anyhow;
+    use reqwest::{
+        StatusCode,
+        header::{HeaderMap, RETRY_AFTER},
+    };
+
+    use super::*;
+
+    fn settings() -> RetryConfig {
+        RetryConfig {
+            timeout_secs: 1,
+            max_retries: 2,
+            backoff_ms: 1,
+            max_backoff_ms: 1000,
+        }
+    }
+
+    fn api_error(status: StatusCode) -> anyhow::Error {
+        anyhow::Error::new(ApiError::new(status, &HeaderMap::new(), "failed"))
+            .context("Failed to get response")
+    }
+
+    #[tokio::test]
+    async fn test_retries_until_success() {
+        let attempts = Cell::new(0);
+        let mut retries = 0;
+
+        let result = with_retries(&settings(), &mut retries, || async {
+            attempts.set(attempts.get() + 1);
+            if attempts.get() < 3 {
+                Err(api_error(StatusCode::BAD_GATEWAY))
+            } else {
+                Ok("ls")
+            }
+        })
+        .await;
+
+        assert_eq!(result.unwrap(), "ls");
+        assert_eq!(retries, 2);
+    }
+
+    #[tokio::test]
+    async fn test_gives_up_after_max_retries() {
+        let mut retries = 0;
+        let result: Result<()> = with_retries(&settings(), &mut retries, || async {
+            Err(api_error(StatusCode::TOO_MANY_REQUESTS))
+        })
+        .await;
+
+        assert!(result.is_err());
+        assert_eq!(retries, 2);
+    }
+
+    #[tokio::test]
+    async fn test_does_not_retry_client_errors() {
+        let mut retries = 0;
+        let result: Result<()> = with_retries(&settings(), &mut retries, || async {
+            Err(api_error(StatusCode::UNAUTHORIZED))
+        })
+        .await;
+
+        assert!(result.is_err());
+        assert_eq!(retries, 0);
+    }
+
+    #[test]
+    fn test_other_errors_are_not_retryable() {
+        assert!(!is_retryable(&anyhow!("Response is empty")));
+    }
+
+    #[test]
+    fn test_retry_delay_honours_retry_after() {
+        let mut headers = HeaderMap::new();
+        headers.insert(RETRY_AFTER, "1".parse().unwrap());
+        let err = ApiError::new(StatusCode::TOO_MANY_REQUESTS, &headers, "slow down").into();
+        assert_eq!(
+            retry_delay(&settings(), 0, &err),
+            Some(Duration::from_secs(1))
+        );
+
+        // Waiting longer than the configured maximum isn't worth it
+        headers.insert(RETRY_AFTER, "3600".parse().unwrap());
+        let err = ApiError::new(StatusCode::TOO_MANY_REQUESTS, &headers, "slow down").into();
+        assert_eq!(retry_delay(&settings(), 0, &err), None);
+    }
+
+    #[test]
+    fn test_retry_delay_backs_off_exponentially() {
+        let settings = RetryConfig {
+            backoff_ms: 400,
+            max_backoff_ms: 1000,
+            ..settings()
+        };
+        let err = api_error(StatusCode::BAD_GATEWAY);
+
+        let first = retry_delay(&settings, 0, &err).unwrap();
+        assert!((200..=400).contains(&first.as_millis()));
+        let second = retry_delay(&settings, 1, &err).unwrap();
+        assert!((400..=800).contains(&second.as_millis()));
+        let capped = retry_delay(&settings, 5, &err).unwrap();
+        assert!((500..=1000).contains(&capped.as_millis()));
+    }
+}
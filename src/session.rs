@@ -0,0 +1,140 @@
+use std::{env, fs, path::PathBuf};
+
+use anyhow::{Context, Result};
+
+use crate::provider::Message;
+
+/// Only the most recent turns are replayed, to keep requests small
+const MAX_SESSION_MESSAGES: usize = 20;
+
+/// Environment variables identifying the current terminal, most specific first
+const TERMINAL_ID_VARS: [&str; 4] = ["QQ_SESSION", "TMUX_PANE", "TERM_SESSION_ID", "WINDOWID"];
+
+/// Conversation histories stored as JSON files, one per session
+pub struct SessionStore {
+    dir: PathBuf,
+}
+
+impl SessionStore {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self { dir: dir.into() }
+    }
+
+    /// Returns the messages of a session, or nothing if it doesn't exist yet
+    pub fn load(&self, key: &str) -> Result<Vec<Message>> {
+        let path = self.path(key);
+        if !path.exists() {
+            return Ok(Vec::new());
+        }
+
+        let contents =
+            fs::read_to_string(&path).context(format!("Failed to read session {:?}", path))?;
+        serde_json::from_str(&contents).context(format!("Failed to parse session {:?}", path))
+    }
+
+    pub fn save(&self, key: &str, messages: &[Message]) -> Result<()> {
+        fs::create_dir_all(&self.dir)
+            .context(format!("Failed to create sessions dir {:?}", self.dir))?;
+
+        let start = messages.len().saturating_sub(MAX_SESSION_MESSAGES);
+        let json = serde_json::to_string(&messages[start..])?;
+        let path = self.path(key);
+        fs::write(&path, json).context(format!("Failed to write session {:?}", path))
+    }
+
+    fn path(&self, key: &str) -> PathBuf {
+        self.dir.join(format!("{}.json", sanitize_key(key)))
+    }
+}
+
+/// Picks the session to use: an explicit name, the current terminal, or the
+/// parent shell process as a last resort
+pub fn session_key(name: Option<&str>) -> String {
+    if let Some(name) = name {
+        return name.to_string();
+    }
+
+    TERMINAL_ID_VARS
+        .iter()
+        .find_map(|var| env::var(var).ok().filter(|value| !value.is_empty()))
+        .unwrap_or_else(parent_process_key)
+}
+
+#[cfg(unix)]
+fn parent_process_key() -> String {
+    format!("ppid-{}", std::os::unix::process::parent_id())
+}
+
+#[cfg(not(unix))]
+fn parent_process_key() -> String {
+    String::from("default")
+}
+
+/// Keeps session keys usable as file names
+fn sanitize_key(key: &str) -> String {
+    key.chars()
+        .map(|c| match c {
+            'a'..='z' | 'A'..='Z' | '0'..='9' | '-' | '_' => c,
+            _ => '_',
+        })
+        .collect()
+}
+
+#[cfg(test)]
+#[cfg_attr(coverage_nightly, coverage(off))]
+mod tests {
+    use super::*;
+
+    fn temp_store(name: &str) -> SessionStore {
+        let dir = env::temp_dir().join(format!("qq-sessions-{}-{}", name, std::process::id()));
+        SessionStore::new(dir)
+    }
+
+    #[test]
+    fn test_load_missing_session() {
+        let store = temp_store("missing");
+        assert!(store.load("nothing-here").unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_save_and_load() {
+        let store = temp_store("roundtrip");
+        let messages = vec![
+            Message::user("find large files"),
+            Message::assistant("find . -size +100M"),
+        ];
+
+        store.save("work", &messages).unwrap();
+        let loaded = store.load("work").unwrap();
+        fs::remove_dir_all(&store.dir).unwrap();
+
+        assert_eq!(loaded, messages);
+    }
+
+    #[test]
+    fn test_save_keeps_recent_messages() {
+        let store = temp_store("capped");
+        let messages: Vec<_> = (0..MAX_SESSION_MESSAGES + 4)
+            .map(|i| Message::user(i.to_string()))
+            .collect();
+
+        store.save("long", &messages).unwrap();
+        let loaded = store.load("long").unwrap();
+        fs::remove_dir_all(&store.dir).unwrap();
+
+        assert_eq!(loaded.len(), MAX_SESSION_MESSAGES);
+        assert_eq!(loaded[0].content, "4");
+    }
+
+    #[test]
+    fn test_session_key_prefers_name() {
+        assert_eq!(session_key(Some("refactor")), "refactor");
+    }
+
+    #[test]
+    fn test_sanitize_key() {
+        assert_eq!(sanitize_key("%1"), "_1");
+        assert_eq!(sanitize_key("../etc/passwd"), "___etc_passwd");
+        assert_eq!(sanitize_key("w0t0p0:6B2A-41F0"), "w0t0p0_6B2A-41F0");
+    }
+}
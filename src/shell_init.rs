@@ -0,0 +1,118 @@
+use clap::ValueEnum;
+
+/// Shells with a widget for `qq shell-init`
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq)]
+pub enum Shell {
+    Zsh,
+    Bash,
+    Fish,
+}
+
+// Each widget sends the command line to qq and replaces it with the answer,
+// leaving it untouched if the request fails. qq prints errors to stderr, which
+// the widgets leave on the terminal. The hooks record the last command and its
+// exit status for `qq fix`.
+const ZSH_WIDGET: &str = r#"# qq widget: press Ctrl-G to turn the command line into a qq query
+_qq_widget() {
+  [[ -n $BUFFER ]] || return
+  local result
+  zle -I
+  if result=$(qq -- "$BUFFER"); then
+    BUFFER=$result
+    CURSOR=${#BUFFER}
+  fi
+  zle reset-prompt
+}
+zle -N _qq_widget
+bindkey '^G' _qq_widget
+
+_qq_preexec() {
+  _qq_command=$1
+}
+_qq_precmd() {
+  local exit_code=$?
+  [[ -n $_qq_command ]] || return
+  export QQ_LAST_COMMAND=$_qq_command QQ_LAST_STATUS=$exit_code
+  unset _qq_command
+}
+autoload -Uz add-zsh-hook
+add-zsh-hook preexec _qq_preexec
+add-zsh-hook precmd _qq_precmd
+"#;
+
+const BASH_WIDGET: &str = r#"# qq widget: press Ctrl-G to turn the command line into a qq query
+_qq_widget() {
+  [[ -n $READLINE_LINE ]] || return
+  local result
+  if result=$(qq -- "$READLINE_LINE"); then
+    READLINE_LINE=$result
+    READLINE_POINT=${#READLINE_LINE}
+  fi
+}
+bind -x '"\C-g": _qq_widget'
+
+_qq_precmd() {
+  local exit_code=$?
+  export QQ_LAST_STATUS=$exit_code
+  export QQ_LAST_COMMAND=$(HISTTIMEFORMAT= history 1 | sed 's/^ *[0-9]* *//')
+  return $exit_code
+}
+PROMPT_COMMAND="_qq_precmd${PROMPT_COMMAND:+;$PROMPT_COMMAND}"
+"#;
+
+const FISH_WIDGET: &str = r#"# qq widget: press Ctrl-G to turn the command line into a qq query
+function __qq_widget
+    set -l buffer (commandline)
+    test -n "$buffer"; or return
+    set -l result
+    if set result (qq -- "$buffer")
+        commandline -r -- (string join \n -- $result)
+    end
+    commandline -f repaint
+end
+bind \cg __qq_widget
+
+function __qq_postexec --on-event fish_postexec
+    set -gx QQ_LAST_STATUS $status
+    set -gx QQ_LAST_COMMAND $argv[1]
+end
+"#;
+
+/// Returns the script to source from the shell's startup file
+pub fn widget_script(shell: Shell) -> &'static str {
+    match shell {
+        Shell::Zsh => ZSH_WIDGET,
+        Shell::Bash => BASH_WIDGET,
+        Shell::Fish => FISH_WIDGET,
+    }
+}
+
+#[cfg(test)]
+#[cfg_attr(coverage_nightly, coverage(off))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_widgets_pass_buffer_as_question() {
+        // `--` keeps a buffer like "use ..." from being parsed as a subcommand
+        assert!(widget_script(Shell::Zsh).contains(r#"qq -- "$BUFFER""#));
+        assert!(widget_script(Shell::Bash).contains(r#"qq -- "$READLINE_LINE""#));
+        assert!(widget_script(Shell::Fish).contains(r#"qq -- "$buffer""#));
+    }
+
+    #[test]
+    fn test_hooks_record_last_command() {
+        for shell in [Shell::Zsh, Shell::Bash, Shell::Fish] {
+            let script = widget_script(shell);
+            assert!(script.contains("QQ_LAST_COMMAND"), "{shell:?}");
+            assert!(script.contains("QQ_LAST_STATUS"), "{shell:?}");
+        }
+    }
+
+    #[test]
+    fn test_widgets_bind_ctrl_g() {
+        assert!(widget_script(Shell::Zsh).contains("bindkey '^G'"));
+        assert!(widget_script(Shell::Bash).contains(r#"bind -x '"\C-g"#));
+        assert!(widget_script(Shell::Fish).contains(r"bind \cg"));
+    }
+}
@@ -0,0 +1,251 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    path::PathBuf,
+};
+
+use anyhow::Result;
+use chrono::DateTime;
+use clap::ValueEnum;
+
+use crate::{
+    configs::ModelPrice,
+    logging::{RequestLogEntry, read_log},
+    pricing::request_cost,
+    provider::Provider,
+};
+
+/// How `qq stats` groups requests
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq)]
+pub enum StatsGroup {
+    Day,
+    Model,
+    Provider,
+}
+
+/// Aggregated numbers for a group of requests
+#[derive(Debug, Default, PartialEq)]
+struct Totals {
+    requests: usize,
+    errors: usize,
+    prompt_tokens: u64,
+    completion_tokens: u64,
+    cost: f64,
+    latency_ms: u64,
+}
+
+impl Totals {
+    fn add(&mut self, entry: &RequestLogEntry, prices: &HashMap<String, ModelPrice>) {
+        self.requests += 1;
+        if !entry.error.is_empty() {
+            self.errors += 1;
+        }
+        if let Some(usage) = &entry.usage {
+            self.prompt_tokens += u64::from(usage.prompt_tokens);
+            self.completion_tokens += u64::from(usage.completion_tokens);
+        }
+        // Requests logged before a price was configured are priced now
+        let cost = entry.cost_usd.or_else(|| {
+            let usage = entry.usage.as_ref()?;
+            request_cost(usage, &entry.config.model, prices)
+        });
+        self.cost += cost.unwrap_or_default();
+        self.latency_ms += entry.llm_response_time_ms;
+    }
+
+    fn average_latency_ms(&self) -> u64 {
+        self.latency_ms / self.requests.max(1) as u64
+    }
+}
+
+/// Prints spend, tokens and latency from the request log, grouped by `group`
+pub fn show_stats(
+    log_file: Option<&PathBuf>,
+    group: StatsGroup,
+    prices: &HashMap<String, ModelPrice>,
+) -> Result<()> {
+    let entries = read_log(log_file)?;
+    if entries.is_empty() {
+        println!("No requests logged yet");
+        return Ok(());
+    }
+
+    let groups = aggregate(&entries, group, prices);
+    let mut total = Totals::default();
+    for entry in &entries {
+        total.add(entry, prices);
+    }
+
+    let title = match group {
+        StatsGroup::Day => "DAY",
+        StatsGroup::Model => "MODEL",
+        StatsGroup::Provider => "PROVIDER",
+    };
+    let width = groups
+        .keys()
+        .map(|key| key.len())
+        .chain([title.len(), "TOTAL".len()])
+        .max()
+        .unwrap_or_default();
+
+    println!(
+        "\x1b[1m{:<width$}  {:>8}  {:>6}  {:>13}  {:>17}  {:>10}  {:>11}\x1b[0m",
+        title, "REQUESTS", "ERRORS", "PROMPT TOKENS", "COMPLETION TOKENS", "COST", "AVG LATENCY"
+    );
+    for (key, totals) in &groups {
+        print_row(key, totals, width);
+    }
+    print_row("TOTAL", &total, width);
+    Ok(())
+}
+
+fn print_row(key: &str, totals: &Totals, width: usize) {
+    println!(
+        "{:<width$}  {:>8}  {:>6}  {:>13}  {:>17}  {:>10}  {:>11}",
+        key,
+        totals.requests,
+        totals.errors,
+        totals.prompt_tokens,
+        totals.completion_tokens,
+        format!("${:.4}", totals.cost),
+        format!("{} ms", totals.average_latency_ms()),
+    );
+}
+
+fn aggregate(
+    entries: &[RequestLogEntry],
+    group: StatsGroup,
+    prices: &HashMap<String, ModelPrice>,
+) -> BTreeMap<String, Totals> {
+    let mut groups: BTreeMap<String, Totals> = BTreeMap::new();
+    for entry in entries {
+        groups
+            .entry(group_key(entry, group))
+            .or_default()
+            .add(entry, prices);
+    }
+    groups
+}
+
+fn group_key(entry: &RequestLogEntry, group: StatsGroup) -> String {
+    match group {
+        StatsGroup::Day => DateTime::parse_from_rfc3339(&entry.time)
+            .map(|time| time.date_naive().to_string())
+            .unwrap_or_else(|_| String::from("unknown")),
+        StatsGroup::Model => entry.config.model.clone(),
+        // Older entries logged the provider as e.g. "OpenRouter"
+        StatsGroup::Provider => {
+            let Ok(provider) = entry.config.provider.parse::<Provider>();
+            provider.to_string()
+        }
+    }
+}
+
+#[cfg(test)]
+#[cfg_attr(coverage_nightly, coverage(off))]
+mod tests {
+    use super::*;
+    use crate::{logging::ConfigForLogging, pricing::price_table, provider::TokenUsage};
+
+    fn entry(time: &str, provider: &str, model: &str, cost_usd: Option<f64>) -> RequestLogEntry {
+        RequestLogEntry {
+            time: time.to_string(),
+            config: ConfigForLogging {
+                provider: provider.to_string(),
+                model: model.to_string(),
+                persona: Some("default".to_string()),
+                auto_copy: false,
+            },
+            user_prompt: "list files".to_string(),
+            attached_files: vec![],
+            response: "ls".to_string(),
+            error: String::new(),
+            llm_response_time_ms: 400,
+            cached: false,
+            retries: 0,
+            failed_attempts: vec![],
+            usage: Some(TokenUsage {
+                prompt_tokens: 1000,
+                completion_tokens: 100,
+                cost: None,
+            }),
+            cost_usd,
+            safety_warnings: vec![],
+            executed_command: None,
+            exit_code: None,
+            total_runtime_ms: 450,
+        }
+    }
+
+    fn entries() -> Vec<RequestLogEntry> {
+        let mut failed = entry(
+            "2025-01-20T09:00:00+01:00",
+            "openai",
+            "gpt-4o",
+            Some(0.0035),
+        );
+        failed.error = "rate limited".to_string();
+        failed.llm_response_time_ms = 200;
+
+        vec![
+            entry(
+                "2025-01-19T10:00:00+01:00",
+                "openrouter",
+                "anthropic/claude-3.5-sonnet",
+                Some(0.01),
+            ),
+            entry(
+                "2025-01-19T23:30:00+01:00",
+                "openai",
+                "gpt-4o",
+                Some(0.0035),
+            ),
+            failed,
+        ]
+    }
+
+    #[test]
+    fn test_aggregate_by_day() {
+        let groups = aggregate(&entries(), StatsGroup::Day, &price_table(None));
+        assert_eq!(
+            groups.keys().collect::<Vec<_>>(),
+            ["2025-01-19", "2025-01-20"]
+        );
+
+        let day = &groups["2025-01-19"];
+        assert_eq!(day.requests, 2);
+        assert_eq!(day.prompt_tokens, 2000);
+        assert!((day.cost - 0.0135).abs() < 1e-9);
+        assert_eq!(day.average_latency_ms(), 400);
+    }
+
+    #[test]
+    fn test_aggregate_by_model_and_provider() {
+        let groups = aggregate(&entries(), StatsGroup::Model, &price_table(None));
+        assert_eq!(groups["gpt-4o"].requests, 2);
+        assert_eq!(groups["gpt-4o"].errors, 1);
+        assert_eq!(groups["gpt-4o"].average_latency_ms(), 300);
+
+        let groups = aggregate(&entries(), StatsGroup::Provider, &price_table(None));
+        assert_eq!(groups["openrouter"].requests, 1);
+        assert_eq!(groups["openai"].requests, 2);
+    }
+
+    #[test]
+    fn test_group_old_and_new_provider_names() {
+        let entries = [
+            entry("2025-01-19T10:00:00+01:00", "OpenRouter", "gpt-4o", None),
+            entry("2025-01-20T10:00:00+01:00", "openrouter", "gpt-4o", None),
+            entry("2025-01-20T11:00:00+01:00", "groq", "llama-3.3-70b", None),
+        ];
+        let groups = aggregate(&entries, StatsGroup::Provider, &price_table(None));
+        assert_eq!(groups.keys().collect::<Vec<_>>(), ["groq", "openrouter"]);
+        assert_eq!(groups["openrouter"].requests, 2);
+    }
+
+    #[test]
+    fn test_cost_estimated_for_old_entries() {
+        let entries = [entry("2025-01-19T10:00:00+01:00", "openai", "gpt-4o", None)];
+        let groups = aggregate(&entries, StatsGroup::Model, &price_table(None));
+        assert!((groups["gpt-4o"].cost - 0.0035).abs() < 1e-9);
+    }
+}
@@ -0,0 +1,189 @@
+use std::{env, fs, path::Path};
+
+use crate::configs::SystemConfig;
+
+/// Facts about the machine the answer will be used on
+#[derive(Debug, Clone, PartialEq)]
+pub struct SystemInfo {
+    pub os: String,
+    pub distribution: Option<String>,
+    pub shell: Option<String>,
+    pub cwd: Option<String>,
+}
+
+impl SystemInfo {
+    /// Detects the local system, preferring values set in the config. Setting
+    /// `os` describes another machine (e.g. one reached over ssh), so nothing
+    /// else is detected then.
+    pub fn detect(overrides: &SystemConfig) -> Self {
+        if let Some(os) = &overrides.os {
+            return Self {
+                os: os.clone(),
+                distribution: overrides.distribution.clone(),
+                shell: overrides.shell.clone(),
+                cwd: None,
+            };
+        }
+
+        let distribution = overrides.distribution.clone().or_else(|| {
+            fs::read_to_string("/etc/os-release")
+                .ok()
+                .and_then(|contents| parse_os_release(&contents))
+        });
+        let shell = overrides
+            .shell
+            .clone()
+            .or_else(|| env::var("SHELL").ok().and_then(|shell| shell_name(&shell)));
+        let cwd = env::current_dir().ok().map(|cwd| cwd.display().to_string());
+
+        Self {
+            os: env::consts::OS.to_string(),
+            distribution,
+            shell,
+            cwd,
+        }
+    }
+
+    /// Renders the system description appended to the system prompt
+    pub fn describe(&self) -> String {
+        let mut description = match &self.distribution {
+            Some(distribution) => format!(
+                "You are running on {} ({}), make sure to return a compatible command",
+                self.os, distribution
+            ),
+            None => format!(
+                "You are running on {}, make sure to return a compatible command",
+                self.os
+            ),
+        };
+        if let Some(shell) = &self.shell {
+            description.push_str(&format!("\nThe user's shell is {shell}"));
+        }
+        if let Some(cwd) = &self.cwd {
+            description.push_str(&format!("\nThe current working directory is {cwd}"));
+        }
+        description
+    }
+}
+
+/// Extracts a human-readable distribution name from `/etc/os-release`
+fn parse_os_release(contents: &str) -> Option<String> {
+    let value = |key: &str| {
+        contents.lines().find_map(|line| {
+            line.strip_prefix(key)
+                .and_then(|rest| rest.strip_prefix('='))
+                .map(|value| value.trim().trim_matches('"').to_string())
+                .filter(|value| !value.is_empty())
+        })
+    };
+    value("PRETTY_NAME").or_else(|| value("NAME"))
+}
+
+fn shell_name(shell_path: &str) -> Option<String> {
+    Path::new(shell_path)
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .filter(|name| !name.is_empty())
+}
+
+#[cfg(test)]
+#[cfg_attr(coverage_nightly, coverage(off))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_os_release() {
+        let contents = r#"NAME="Ubuntu"
+VERSION_ID="22.04"
+PRETTY_NAME="Ubuntu 22.04.4 LTS"
+ID=ubuntu"#;
+        assert_eq!(
+            parse_os_release(contents),
+            Some("Ubuntu 22.04.4 LTS".to_string())
+        );
+    }
+
+    #[test]
+    fn test_parse_os_release_falls_back_to_name() {
+        assert_eq!(
+            parse_os_release("NAME=Alpine\nID=alpine"),
+            Some("Alpine".to_string())
+        );
+        assert_eq!(parse_os_release("ID=unknown"), None);
+    }
+
+    #[test]
+    fn test_shell_name() {
+        assert_eq!(shell_name("/bin/zsh"), Some("zsh".to_string()));
+        assert_eq!(
+            shell_name("/opt/homebrew/bin/fish"),
+            Some("fish".to_string())
+        );
+        assert_eq!(shell_name(""), None);
+    }
+
+    #[test]
+    fn test_detect_uses_overrides() {
+        let overrides = SystemConfig {
+            os: Some("linux".to_string()),
+            distribution: Some("Debian 12".to_string()),
+            shell: Some("bash".to_string()),
+        };
+
+        let info = SystemInfo::detect(&overrides);
+        assert_eq!(info.os, "linux");
+        assert_eq!(info.distribution.as_deref(), Some("Debian 12"));
+        assert_eq!(info.shell.as_deref(), Some("bash"));
+    }
+
+    #[test]
+    fn test_detect_only_uses_overrides_for_other_os() {
+        let overrides = SystemConfig {
+            os: Some("freebsd".to_string()),
+            ..Default::default()
+        };
+
+        let info = SystemInfo::detect(&overrides);
+        assert_eq!(
+            info,
+            SystemInfo {
+                os: "freebsd".to_string(),
+                distribution: None,
+                shell: None,
+                cwd: None,
+            }
+        );
+    }
+
+    #[test]
+    fn test_describe() {
+        let info = SystemInfo {
+            os: "linux".to_string(),
+            distribution: Some("Fedora Linux 40".to_string()),
+            shell: Some("zsh".to_string()),
+            cwd: Some("/home/dev/project".to_string()),
+        };
+
+        assert_eq!(
+            info.describe(),
+            "You are running on linux (Fedora Linux 40), make sure to return a compatible command\n\
+             The user's shell is zsh\n\
+             The current working directory is /home/dev/project"
+        );
+    }
+
+    #[test]
+    fn test_describe_minimal() {
+        let info = SystemInfo {
+            os: "macos".to_string(),
+            distribution: None,
+            shell: None,
+            cwd: None,
+        };
+
+        assert_eq!(
+            info.describe(),
+            "You are running on macos, make sure to return a compatible command"
+        );
+    }
+}